@@ -1,8 +1,15 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use mice::{builder::RollBuilder, FormatOptions};
 
 fn rolling_benchmark(c: &mut Criterion) {
     c.bench_function("rolls", |b| b.iter(|| mice::roll(black_box("100000d100"))));
 }
 
-criterion_group!(benches, rolling_benchmark);
+fn slim_rolling_benchmark(c: &mut Criterion) {
+    let mut roll = RollBuilder::new().parse("100000d100").unwrap().into_roll().unwrap();
+    let format = FormatOptions::new().concise();
+    c.bench_function("slim rolls", |b| b.iter(|| roll.slim_roll(black_box(format))));
+}
+
+criterion_group!(benches, rolling_benchmark, slim_rolling_benchmark);
 criterion_main!(benches);
@@ -24,17 +24,20 @@
 //! Note the `.with_rng` call. Without it, `.into_roll()` would
 //! unavoidably panic on trying to construct a `ThreadRng` where
 //! the underlying `rand` crate does not support it.
-// pub use crate::post::FormatOptions;
 use crate::{
+    distribution::Simulation,
     expose::ExprTuple,
-    parse::{wrap_dice, Expr, Expression, ParseError, InvalidDie},
-    post::EResult,
-    roll_expr_iter_with,
+    fair::FairRoll,
+    parse::{wrap_dice_with, Expr, Expression, NamedDice, ParseError, InvalidDie},
+    post::{BatchResult, EResult, ExpressionResult, FormatOptions},
+    roll_expression_with, roll_slim_with, roll_total_with, Checked, Error, Unchecked,
 };
 use std::convert::TryFrom;
+use std::sync::Arc;
 use thiserror::Error;
 
-use rand::{thread_rng, RngCore, rngs::ThreadRng};
+use rand::{thread_rng, RngCore, SeedableRng, rngs::ThreadRng};
+use rand_pcg::Pcg32;
 #[derive(Debug, Error)]
 pub enum BuildError {
     #[error("builder given no expression")]
@@ -44,15 +47,34 @@ pub enum BuildError {
 #[derive(Default)]
 pub struct RollBuilder {
     expression: Option<Expression>,
+    named: NamedDice,
 }
 impl RollBuilder {
     pub fn new() -> RollBuilder {
         RollBuilder {
             expression: None,
+            named: NamedDice::new(),
         }
     }
+    /// Define a die with custom faces, for use in expressions
+    /// parsed after this, like `dBoost`.
+    /// See `parse::NamedDice::define` for what names are allowed.
+    /// ```
+    /// # use mice::builder::RollBuilder;
+    /// let result = RollBuilder::new()
+    ///     .define_die("Boost", &[0, 0, 1, 1, 2, 2])?
+    ///     .parse("2dBoost + 1")?
+    ///     .into_roll()?
+    ///     .roll()?;
+    /// assert!((1..=5).contains(&result.total()));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn define_die(mut self, name: &str, faces: &[i64]) -> Result<RollBuilder, InvalidDie> {
+        self.named.define(name, faces)?;
+        Ok(self)
+    }
     pub fn parse(mut self, input: &str) -> Result<RollBuilder, ParseError> {
-        let expression = wrap_dice(input)?;
+        let expression = wrap_dice_with(input, &self.named)?;
         self.expression = Some(expression);
         Ok(self)
     }
@@ -64,8 +86,8 @@ impl RollBuilder {
         self.expression = Some(Expression::new(expression));
         Ok(self)
     }
-    #[allow(dead_code)]
-    pub(crate) fn with_expression(mut self, expression: Expression) -> RollBuilder {
+    /// Roll an already built expression, as from `parse::ExpressionBuilder`.
+    pub fn with_expression(mut self, expression: Expression) -> RollBuilder {
         self.expression = Some(expression);
         self
     }
@@ -77,6 +99,7 @@ impl RollBuilder {
         RollBuilderWithRng {
             generator: rng,
             expression: self.expression,
+            named: self.named,
         }
     }
     /// `into_roll()` can only be used without specifying an RNG
@@ -88,11 +111,42 @@ impl RollBuilder {
             generator: thread_rng(),
         })
     }
+    /// Make a replayable roll, with a seed drawn from `thread_rng`.
+    #[cfg(feature = "thread_rng")]
+    pub fn into_replayable(self) -> Result<ReplayableRoll, BuildError> {
+        self.into_seeded_roll(thread_rng().next_u64())
+    }
+    /// Make a replayable roll whose first result uses the given seed.
+    /// ```
+    /// # use mice::builder::{replay, RollBuilder};
+    /// let mut roll = RollBuilder::new().parse("4d6kh3")?.into_seeded_roll(1234)?;
+    /// let result = roll.roll()?;
+    /// let record = result.replay().unwrap();
+    /// assert_eq!(record.seed(), 1234);
+    /// assert_eq!(replay(record)?.to_string(), result.to_string());
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn into_seeded_roll(self, seed: u64) -> Result<ReplayableRoll, BuildError> {
+        Ok(ReplayableRoll::new(self.expression.ok_or(BuildError::NoExpression)?, seed))
+    }
+    /// Make a fair roll, with a server seed drawn from `thread_rng`.
+    #[cfg(feature = "thread_rng")]
+    pub fn into_fair_roll(self) -> Result<FairRoll, BuildError> {
+        let mut server_seed = [0; 32];
+        thread_rng().fill_bytes(&mut server_seed);
+        self.into_committed_roll(server_seed)
+    }
+    /// Make a fair roll committed to the given server seed.
+    /// See the `fair` module for how it's used.
+    pub fn into_committed_roll(self, server_seed: [u8; 32]) -> Result<FairRoll, BuildError> {
+        Ok(FairRoll::new(self.expression.ok_or(BuildError::NoExpression)?, server_seed))
+    }
 }
 
 pub struct RollBuilderWithRng<R: RngCore> {
     expression: Option<Expression>,
     generator: R,
+    named: NamedDice,
 }
 impl<R: RngCore> RollBuilderWithRng<R> {
     pub fn into_roll(self) -> Result<Roll<R>, BuildError> {
@@ -101,10 +155,26 @@ impl<R: RngCore> RollBuilderWithRng<R> {
             generator: self.generator,
         })
     }
+    /// Make a replayable roll, with a seed drawn from the given RNG.
+    pub fn into_replayable(mut self) -> Result<ReplayableRoll, BuildError> {
+        let seed = self.generator.next_u64();
+        Ok(ReplayableRoll::new(self.expression.ok_or(BuildError::NoExpression)?, seed))
+    }
+    /// Make a fair roll, with a server seed drawn from the given RNG,
+    /// which should be cryptographically secure.
+    pub fn into_fair_roll(mut self) -> Result<FairRoll, BuildError> {
+        let mut server_seed = [0; 32];
+        self.generator.fill_bytes(&mut server_seed);
+        Ok(FairRoll::new(self.expression.ok_or(BuildError::NoExpression)?, server_seed))
+    }
 
     // DUPLICATED CODE:
+    pub fn define_die(mut self, name: &str, faces: &[i64]) -> Result<Self, InvalidDie> {
+        self.named.define(name, faces)?;
+        Ok(self)
+    }
     pub fn parse(mut self, input: &str) -> Result<Self, ParseError> {
-        let expression = wrap_dice(input)?;
+        let expression = wrap_dice_with(input, &self.named)?;
         self.expression = Some(expression);
         Ok(self)
     }
@@ -116,8 +186,7 @@ impl<R: RngCore> RollBuilderWithRng<R> {
         self.expression = Some(Expression::new(expression));
         Ok(self)
     }
-    #[allow(dead_code)]
-    pub(crate) fn with_expression(mut self, expression: Expression) -> Self {
+    pub fn with_expression(mut self, expression: Expression) -> Self {
         self.expression = Some(expression);
         self
     }
@@ -130,31 +199,183 @@ pub struct Roll<R: RngCore> {
 
 impl<R: RngCore> Roll<R> {
     pub fn roll(&mut self) -> EResult {
-        roll_expr_iter_with(&mut self.generator, self.expression.iter().copied())
-    }
-    // /// Proposed public API
-    // /// For the purpose of performance, discard all information
-    // /// unnecessary for the specified format.
-    // pub(crate) fn slim_roll(&mut self, _formatting: FormatOptions) -> EResult {
-    //     self.roll()
-    // }
+        roll_expression_with::<Checked, _>(&mut self.generator, &self.expression)
+    }
+    /// Roll the expression `count` times, as for a group of identical monsters.
+    /// ```
+    /// # use mice::builder::RollBuilder;
+    /// let goblins = RollBuilder::new().parse("1d20 + 3")?.into_roll()?.roll_batch(12)?;
+    /// assert_eq!(goblins.results().len(), 12);
+    /// assert!(goblins.totals().all(|x| (4..=23).contains(&x)));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn roll_batch(&mut self, count: i64) -> Result<BatchResult, Error> {
+        let results = (0..count).map(|_| self.roll()).collect::<Result<_, _>>()?;
+        Ok(BatchResult::new(results))
+    }
+    /// Roll the expression `trials` times, keeping only the totals.
+    /// Stops at the first roll that fails.
+    /// ```
+    /// # use mice::builder::RollBuilder;
+    /// let simulation = RollBuilder::new().parse("3d6")?.into_roll()?.simulate(1000)?;
+    /// assert_eq!(simulation.trials(), 1000);
+    /// assert!((3..=18).contains(&simulation.percentile(50.0).unwrap()));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn simulate(&mut self, trials: u64) -> Result<Simulation, Error> {
+        let mut simulation = Simulation::default();
+        for _ in 0..trials {
+            simulation.record(roll_total_with::<Checked, _>(&mut self.generator, &self.expression)?);
+        }
+        Ok(simulation)
+    }
+    /// For the purpose of performance, discard all information
+    /// unnecessary for the specified format.
+    ///
+    /// With `FormatOptions::concise`, individual dice aren't kept,
+    /// so even huge numbers of dice are rolled in constant memory.
+    /// Neither are their crits, so `ExpressionResult::criticals` counts none.
+    /// ```
+    /// # use mice::{builder::RollBuilder, FormatOptions};
+    /// let format = FormatOptions::new().concise().total_right();
+    /// let result = RollBuilder::new().parse("100000d100")?.into_roll()?.slim_roll(format)?;
+    /// assert!((100000..=10000000).contains(&result.total()));
+    /// println!("{}", result.format(format));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn slim_roll(&mut self, formatting: FormatOptions) -> EResult {
+        if formatting.summarize_terms {
+            roll_slim_with::<Checked, _>(&mut self.generator, &self.expression)
+        } else {
+            self.roll()
+        }
+    }
 }
 
-// /// A roll that has been preemptively verified to be safe,
-// /// and thus requires no internal bounds checks.
-// /// The space of safe rolls is smaller than the space
-// /// of unsafe but still potentially valid rolls.
-// /// Still, `i64`s are large enough that this isn't likely
-// /// a concern for dice rolling.
-// /// Since obtaining this performs the same checks as using
-// /// a `Roll`, it is only worth doing if the same expression
-// /// is going to be used more than once.
-// struct SafeRoll {
-//     expression: Expression,
-//     generator: Box<dyn RngCore>,
-// }
-// impl SafeRoll {
-//     pub fn roll(&mut self) -> ExpressionResult {
+/// The seed and expression of a replayable roll,
+/// everything needed to roll it again identically.
+#[derive(Debug, Clone)]
+pub struct Replay {
+    seed: u64,
+    expression: Arc<Expression>,
+}
+impl Replay {
+    /// Reconstruct a record, as from a seed and expression kept elsewhere.
+    pub fn new(seed: u64, expression: Expression) -> Self {
+        Replay {
+            seed,
+            expression: Arc::new(expression),
+        }
+    }
+    pub(crate) fn shared(seed: u64, expression: Arc<Expression>) -> Self {
+        Replay { seed, expression }
+    }
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+    pub fn expression(&self) -> &Expression {
+        &self.expression
+    }
+}
+
+/// Roll an expression again with the seed it was rolled with.
+/// The result is identical on every platform, down to each die.
+pub fn replay(record: &Replay) -> EResult {
+    // `Pcg32` is portable, and its output is fixed by `rand_pcg`,
+    // unlike the algorithm behind `StdRng`.
+    let mut generator = Pcg32::seed_from_u64(record.seed);
+    let result = roll_expression_with::<Checked, _>(&mut generator, &record.expression)?;
+    Ok(result.with_replay(record.clone()))
+}
+
+/// A roll whose results each record the seed they were rolled with,
+/// so they can be replayed when disputed.
+///
+/// The first result uses the seed this was made with,
+/// and the seeds of later results follow from it.
+pub struct ReplayableRoll {
+    expression: Arc<Expression>,
+    seed: u64,
+    seeds: Pcg32,
+}
+impl ReplayableRoll {
+    fn new(expression: Expression, seed: u64) -> Self {
+        ReplayableRoll {
+            expression: Arc::new(expression),
+            seed,
+            // Kept apart from the dice of the first result,
+            // which are drawn from `Pcg32::seed_from_u64(seed)`.
+            seeds: Pcg32::new(seed, 0),
+        }
+    }
+    /// The seed the next result will use.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+    pub fn roll(&mut self) -> EResult {
+        let record = Replay::shared(self.seed, self.expression.clone());
+        self.seed = self.seeds.next_u64();
+        replay(&record)
+    }
+}
 
-//     }
-// }
+/// A roll that has been preemptively verified to be safe,
+/// and thus requires no internal bounds checks.
+/// The space of safe rolls is smaller than the space
+/// of unsafe but still potentially valid rolls.
+/// Still, `i64`s are large enough that this isn't likely
+/// a concern for dice rolling.
+/// Since obtaining this performs the same checks as using
+/// a `Roll`, it is only worth doing if the same expression
+/// is going to be used more than once.
+/// ```
+/// # use mice::builder::{RollBuilder, SafeRoll};
+/// let mut initiative = SafeRoll::new(RollBuilder::new().parse("d20 + 3")?.into_roll()?)?;
+/// assert_eq!((initiative.min(), initiative.max()), (4, 23));
+/// for _ in 0..10 {
+///     assert!((4..=23).contains(&initiative.total()));
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct SafeRoll<R: RngCore> {
+    expression: Expression,
+    generator: R,
+    bounds: (i64, i64),
+}
+impl<R: RngCore> SafeRoll<R> {
+    /// Check the worst cases of a roll's expression, refusing it
+    /// if any possible roll would overflow or divide by zero.
+    pub fn new(roll: Roll<R>) -> Result<Self, Error> {
+        Ok(SafeRoll {
+            bounds: roll.expression.bounds()?,
+            expression: roll.expression,
+            generator: roll.generator,
+        })
+    }
+    /// Lowest total this can roll.
+    pub fn min(&self) -> i64 {
+        self.bounds.0
+    }
+    /// Highest total this can roll.
+    pub fn max(&self) -> i64 {
+        self.bounds.1
+    }
+    pub fn roll(&mut self) -> ExpressionResult {
+        roll_expression_with::<Unchecked, _>(&mut self.generator, &self.expression)
+            .expect("expression was checked when made safe")
+    }
+    /// Roll, keeping nothing but the total.
+    pub fn total(&mut self) -> i64 {
+        roll_total_with::<Unchecked, _>(&mut self.generator, &self.expression)
+            .expect("expression was checked when made safe")
+    }
+    /// See `Roll::slim_roll`.
+    pub fn slim_roll(&mut self, formatting: FormatOptions) -> ExpressionResult {
+        if formatting.summarize_terms {
+            roll_slim_with::<Unchecked, _>(&mut self.generator, &self.expression)
+                .expect("expression was checked when made safe")
+        } else {
+            self.roll()
+        }
+    }
+}
@@ -1,51 +1,52 @@
 //! Formatting for dice expression results.
-use crate::parse::{Expr, Sign, Term};
-use crate::post::{EvaluatedTerm, ExpressionResult, FormatOptions, TermSeparator, TotalPosition};
+use crate::parse::{Expr, Node, Sign, Term};
+use crate::post::{EvaluatedTerm, ExpressionResult, ExpressionSeparator, FormatOptions, TermSeparator, TotalPosition};
+use std::slice;
 
-/// `[T[ = ]](EXP → N [+ N]*) [+ (EXP → N [+ N]*)]*[[ = ]T]`
+type EvaluatedNode = Node<(Expr, EvaluatedTerm)>;
+
+/// `[LABEL: ][T[ = ]](EXP → N [+ N]*) [+ (EXP → N [+ N]*)]*[[ = ]T]`
 /// Main entry point for formatting the results of dice expressions.
 pub(crate) fn format(e: &ExpressionResult, options: FormatOptions) -> String {
+    labeled(e.label(), format_unlabeled(e, options))
+}
+
+fn labeled(label: Option<&str>, body: String) -> String {
+    match label {
+        Some(label) => format!("{}: {}", label, body),
+        None => body,
+    }
+}
+
+fn format_unlabeled(e: &ExpressionResult, options: FormatOptions) -> String {
     let FormatOptions {
         total_position,
         term_separators,
         term_list_parentheses,
         ..
     } = options;
-    let pairs = e.pairs();
-    let listing = pairs.len() > 1 || pairs[0].1.parts().len() > 1;
+    let operands = match e.tree() {
+        Node::Sum(xs) => xs.as_slice(),
+        x => slice::from_ref(x),
+    };
+    let listing = operands.len() > 1
+        || match &operands[0] {
+            Node::Term((_, after)) => after.is_listing(),
+            _ => true,
+        };
     let total_sep = if listing { " = " } else { "" };
     let mut nstr = match total_position {
         TotalPosition::Left => format!("{}{}", e.total(), total_sep),
         _ => String::new(),
     };
-    nstr = if pairs.is_empty() {
+    nstr = if operands.is_empty() {
         nstr
     } else if listing {
         // VERBOSE TIME
         if term_list_parentheses {
             nstr.push('(');
         }
-        let mut iter = pairs.iter();
-        let (before, after) = iter.next().unwrap();
-        let mut formatting = options;
-        if let TermSeparator::PlusSign = term_separators {
-            formatting = options.exclude_sign();
-        }
-        let form = |a, b| format_dice_term(a, b, formatting);
-        if let TermSeparator::PlusSign = term_separators {
-            if let Sign::Negative = after.sign() {
-                nstr.push_str("-")
-            }
-        }
-        nstr.push_str(&form(&before, &after));
-        for (before, after) in iter {
-            if let TermSeparator::PlusSign = term_separators {
-                nstr.push_str(&format!(" {} ", after.sign()));
-            } else {
-                nstr.push_str(", ");
-            }
-            nstr.push_str(&form(before, &after));
-        }
+        nstr.push_str(&format_sum(operands, options, term_separators));
         if term_list_parentheses {
             nstr.push(')');
         }
@@ -61,13 +62,103 @@ pub(crate) fn format(e: &ExpressionResult, options: FormatOptions) -> String {
     nstr
 }
 
+/// `[LABEL: ]T, T, T`, or with verbose options, `[LABEL: ]EXP = T, EXP = T`.
+/// Formats the results of a batch roll as a compact list.
+pub(crate) fn format_batch(results: &[ExpressionResult], options: FormatOptions) -> String {
+    let mut options = options;
+    // Each roll in the list needs its total, to tell them apart.
+    if let TotalPosition::Suppressed = options.total_position {
+        options = options.total_right();
+    }
+    let entries: Vec<_> = results
+        .iter()
+        .map(|e| {
+            if options.summarize_terms {
+                e.total().to_string()
+            } else {
+                format_unlabeled(e, options)
+            }
+        })
+        .collect();
+    // Every roll in a batch has the same label.
+    labeled(results.first().and_then(ExpressionResult::label), entries.join(", "))
+}
+
+/// `EXP; EXP; EXP`, or one per line.
+/// Formats the results of several expressions rolled together.
+pub(crate) fn format_multi(results: &[ExpressionResult], options: FormatOptions) -> String {
+    let separator = match options.expression_separators {
+        ExpressionSeparator::Semicolon => "; ",
+        ExpressionSeparator::Newline => "\n",
+    };
+    let entries: Vec<_> = results.iter().map(|e| format(e, options)).collect();
+    entries.join(separator)
+}
+
+fn operand_sign(operand: &EvaluatedNode) -> Sign {
+    match operand {
+        Node::Term((_, after)) => after.sign(),
+        Node::Neg(_) => Sign::Negative,
+        _ => Sign::Positive,
+    }
+}
+
+/// `A + B - C`, or `A, B, -C`
+fn format_sum(operands: &[EvaluatedNode], options: FormatOptions, separators: TermSeparator) -> String {
+    let mut nstr = String::new();
+    let mut formatting = options;
+    if let TermSeparator::PlusSign = separators {
+        formatting = options.exclude_sign();
+    }
+    for (i, operand) in operands.iter().enumerate() {
+        if let TermSeparator::PlusSign = separators {
+            match (i, operand_sign(operand)) {
+                (0, Sign::Negative) => nstr.push('-'),
+                (0, Sign::Positive) => (),
+                (_, sign) => nstr.push_str(&format!(" {} ", sign)),
+            }
+        } else if i > 0 {
+            nstr.push_str(", ");
+        }
+        nstr.push_str(&match (operand, separators) {
+            (Node::Term((before, after)), _) => format_dice_term(before, after, formatting),
+            // The sign's already been written as a separator.
+            (Node::Neg(x), TermSeparator::PlusSign) => format_operand(x, options),
+            (x, _) => format_operand(x, options),
+        });
+    }
+    nstr
+}
+
+/// Format any part of an expression tree, with its own sign.
+fn format_operand(node: &EvaluatedNode, options: FormatOptions) -> String {
+    match node {
+        Node::Term((before, after)) => format_dice_term(before, after, options),
+        Node::Neg(x) => format!("-{}", format_operand(x, options)),
+        // Nested sums are only ever separated by signs,
+        // since commas would be ambiguous with other operators.
+        Node::Sum(xs) => format_sum(xs, options, TermSeparator::PlusSign),
+        Node::Product(lhs, op, rhs) => format!(
+            "{} {} {}",
+            format_operand(lhs, options),
+            op,
+            format_operand(rhs, options)
+        ),
+        Node::Group(x) => format!("({})", format_operand(x, options)),
+        Node::Floor(x) => format!("floor({})", format_operand(x, options)),
+    }
+}
+
 fn format_dice_term(prior: &Expr, val: &EvaluatedTerm, f: FormatOptions) -> String {
     let FormatOptions {
         term_parentheses, ..
     } = f;
     match prior.term {
-        Term::Constant(_) => val.format(f),
-        Term::Dice(_) => {
+        Term::Constant(_) => match &prior.label {
+            Some(label) => format!("{}[{}]", val.format(f), label),
+            None => val.format(f),
+        },
+        Term::Dice(_) | Term::Fate(_) | Term::Percentile(_) => {
             let dice_term = format!("{} → {}", prior.format(f), val.format(f));
             if term_parentheses {
                 format!("({})", dice_term)
@@ -0,0 +1,683 @@
+//! Exact probability distributions of dice expressions,
+//! computed by convolution rather than by rolling.
+//!
+//! ```
+//! # use mice::{parse::Expression, Error};
+//! let (_, expression) = Expression::parse("2d6 + 3").unwrap();
+//! let distribution = expression.unwrap().distribution()?;
+//! assert_eq!(distribution.min(), 5);
+//! assert_eq!(distribution.max(), 15);
+//! assert!((distribution.pmf(10) - 6.0 / 36.0).abs() < 1e-12);
+//! assert!((distribution.mean() - 10.0).abs() < 1e-12);
+//! # Ok::<(), Error>(())
+//! ```
+use crate::error::Error;
+use crate::parse::{Comparison, DiceTerm, Expr, Keep, Node, Operator, Sign, Term, Target};
+use crate::parse::EXPLOSION_LIMIT;
+use crate::{checked_add, checked_mul, floor_div, DivisionByZero, OverflowNegative, OverflowPositive, TooComplex};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+
+/// Greatest number of outcomes a distribution may span, from its minimum to its maximum.
+const WIDTH_LIMIT: i64 = 1 << 20;
+/// Greatest number of steps spent combining distributions,
+/// before giving up on an expression as too complex.
+const WORK_LIMIT: u64 = 100_000_000;
+
+/// The probability of every outcome of a dice expression.
+///
+/// Probabilities are computed exactly, save for the rounding
+/// inherent to `f64` arithmetic. Outcomes too unlikely for an `f64`,
+/// like every die of `2000d6` rolling a 1, are left out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Distribution {
+    min: i64,
+    /// Probability of `min + i` at index `i`.
+    /// Neither the first nor the last is zero.
+    probabilities: Vec<f64>,
+}
+impl Distribution {
+    fn point(outcome: i64) -> Self {
+        Self {
+            min: outcome,
+            probabilities: vec![1.0],
+        }
+    }
+    /// Every outcome in `faces` equally likely, counting duplicates.
+    fn uniform(faces: impl Iterator<Item = i64> + Clone) -> Result<Self, Error> {
+        let min = faces.clone().min().unwrap_or(0);
+        let max = faces.clone().max().unwrap_or(0);
+        let mut probabilities = vec![0.0; width(min, max)?];
+        let count = faces.clone().count() as f64;
+        for face in faces {
+            probabilities[(face - min) as usize] += 1.0 / count;
+        }
+        Ok(Self { min, probabilities })
+    }
+    /// Distribution with the same probabilities as `self`,
+    /// restricted to outcomes where `keep` holds. Total probability may be below one.
+    fn filter(&self, keep: impl Fn(i64) -> bool) -> Self {
+        let probabilities = self
+            .probabilities
+            .iter()
+            .enumerate()
+            .map(|(i, p)| if keep(self.min + i as i64) { *p } else { 0.0 })
+            .collect();
+        Self {
+            min: self.min,
+            probabilities,
+        }
+        .trimmed()
+    }
+    fn scaled(mut self, factor: f64) -> Self {
+        self.probabilities.iter_mut().for_each(|p| *p *= factor);
+        self
+    }
+    fn total(&self) -> f64 {
+        self.probabilities.iter().sum()
+    }
+    /// Strip zero probabilities off the ends, so `min` and `max` are possible outcomes.
+    fn trimmed(mut self) -> Self {
+        let start = self.probabilities.iter().position(|&p| p != 0.0);
+        let end = self.probabilities.iter().rposition(|&p| p != 0.0);
+        match (start, end) {
+            (Some(start), Some(end)) => {
+                self.probabilities.truncate(end + 1);
+                self.probabilities.drain(..start);
+                self.min += start as i64;
+            }
+            _ => self.probabilities.clear(),
+        }
+        self
+    }
+    fn is_empty(&self) -> bool {
+        self.probabilities.is_empty()
+    }
+
+    /// Probability of the outcome being exactly `outcome`.
+    pub fn pmf(&self, outcome: i64) -> f64 {
+        match outcome.checked_sub(self.min) {
+            Some(i) if i >= 0 && (i as u64) < self.probabilities.len() as u64 => self.probabilities[i as usize],
+            _ => 0.0,
+        }
+    }
+    /// Probability of the outcome being at most `outcome`.
+    pub fn cdf(&self, outcome: i64) -> f64 {
+        if outcome < self.min {
+            0.0
+        } else if outcome >= self.max() {
+            1.0
+        } else {
+            self.probabilities[..=(outcome - self.min) as usize].iter().sum()
+        }
+    }
+    pub fn mean(&self) -> f64 {
+        self.iter().map(|(x, p)| x as f64 * p).sum()
+    }
+    pub fn variance(&self) -> f64 {
+        let mean = self.mean();
+        self.iter().map(|(x, p)| (x as f64 - mean).powi(2) * p).sum()
+    }
+    /// Lowest possible outcome.
+    pub fn min(&self) -> i64 {
+        self.min
+    }
+    /// Highest possible outcome.
+    pub fn max(&self) -> i64 {
+        self.min + (self.probabilities.len() as i64 - 1)
+    }
+    /// Probability of the outcome passing `comparison`.
+    ///
+    /// ```
+    /// # use mice::{parse::{Comparison, Expression}, Error};
+    /// let (_, expression) = Expression::parse("1d20 + 5").unwrap();
+    /// let distribution = expression.unwrap().distribution()?;
+    /// assert!((distribution.chance(Comparison::GreaterOrEqual(15)) - 0.55).abs() < 1e-12);
+    /// # Ok::<(), Error>(())
+    /// ```
+    pub fn chance(&self, comparison: Comparison) -> f64 {
+        self.iter().filter(|(x, _)| comparison.matches(*x)).map(|(_, p)| p).sum()
+    }
+    /// Probability of an outcome of `self` being greater
+    /// than an independent outcome of `other`.
+    pub fn chance_greater(&self, other: &Distribution) -> f64 {
+        let mut below = other.iter().peekable();
+        let mut cumulative = 0.0;
+        let mut chance = 0.0;
+        for (x, p) in self.iter() {
+            while let Some((y, q)) = below.peek() {
+                if *y >= x {
+                    break;
+                }
+                cumulative += q;
+                below.next();
+            }
+            chance += p * cumulative;
+        }
+        chance
+    }
+    /// Probability of an outcome of `self` being equal
+    /// to an independent outcome of `other`.
+    pub fn chance_equal(&self, other: &Distribution) -> f64 {
+        self.iter().map(|(x, p)| p * other.pmf(x)).sum()
+    }
+    /// Every possible outcome with its probability, from lowest to highest.
+    pub fn iter(&self) -> impl Iterator<Item = (i64, f64)> + '_ {
+        let min = self.min;
+        self.probabilities
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| **p != 0.0)
+            .map(move |(i, p)| (min + i as i64, *p))
+    }
+}
+
+/// Number of slots needed to hold outcomes from `min` to `max`.
+fn width(min: i64, max: i64) -> Result<usize, Error> {
+    match max.checked_sub(min) {
+        Some(x) if x < WIDTH_LIMIT => Ok(x as usize + 1),
+        _ => Err(TooComplex.into()),
+    }
+}
+
+/// Tracks the work spent on an expression.
+struct Budget(u64);
+impl Budget {
+    fn spend(&mut self, steps: usize) -> Result<(), Error> {
+        self.0 = self.0.saturating_add(steps as u64);
+        if self.0 > WORK_LIMIT {
+            Err(TooComplex.into())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Combine every pair of outcomes of `a` and `b` with `op`.
+///
+/// `op` must reach its extremes with `a` at either end and `b` at either end
+/// or nearest zero on either side, as multiplication and division do.
+fn combine<F>(a: &Distribution, b: &Distribution, budget: &mut Budget, op: F) -> Result<Distribution, Error>
+where
+    F: Fn(i64, i64) -> Result<i64, Error>,
+{
+    if a.is_empty() || b.is_empty() {
+        return Ok(Distribution { min: 0, probabilities: Vec::new() });
+    }
+    let below = b.iter().map(|(y, _)| y).filter(|&y| y < 0).last();
+    let above = b.iter().map(|(y, _)| y).find(|&y| y > 0);
+    let ys = [Some(b.min), Some(b.max()), below, above];
+    let mut min = i64::MAX;
+    let mut max = i64::MIN;
+    for &x in &[a.min, a.max()] {
+        for &y in ys.iter().flatten() {
+            let z = op(x, y)?;
+            min = min.min(z);
+            max = max.max(z);
+        }
+    }
+    let width = width(min, max)?;
+    budget.spend(a.probabilities.len() * b.probabilities.len())?;
+    let mut probabilities = vec![0.0; width];
+    for (x, p) in a.iter() {
+        for (y, q) in b.iter() {
+            probabilities[(op(x, y)? - min) as usize] += p * q;
+        }
+    }
+    Ok(Distribution { min, probabilities }.trimmed())
+}
+
+/// Distribution of the sum of outcomes of `a` and `b`.
+fn convolve(a: &Distribution, b: &Distribution, budget: &mut Budget) -> Result<Distribution, Error> {
+    if a.is_empty() || b.is_empty() {
+        return Ok(Distribution { min: 0, probabilities: Vec::new() });
+    }
+    // Since the ends of both are possible outcomes,
+    // overflow here means some outcome overflows.
+    let min = checked_add(a.min, b.min)?;
+    checked_add(a.max(), b.max())?;
+    let (m, n) = (a.probabilities.len(), b.probabilities.len());
+    budget.spend(m * n)?;
+    width(0, (m + n - 2) as i64)?;
+    let mut probabilities = vec![0.0; m + n - 1];
+    for (i, p) in a.probabilities.iter().enumerate() {
+        if *p == 0.0 {
+            continue;
+        }
+        for (j, q) in b.probabilities.iter().enumerate() {
+            probabilities[i + j] += p * q;
+        }
+    }
+    Ok(Distribution { min, probabilities }.trimmed())
+}
+
+/// Pool the probabilities of two partial distributions, outcome by outcome.
+fn mix(a: Distribution, b: Distribution) -> Result<Distribution, Error> {
+    if a.is_empty() {
+        return Ok(b);
+    } else if b.is_empty() {
+        return Ok(a);
+    }
+    let min = a.min.min(b.min);
+    let max = a.max().max(b.max());
+    let mut probabilities = vec![0.0; width(min, max)?];
+    for (x, p) in a.iter().chain(b.iter()) {
+        probabilities[(x - min) as usize] += p;
+    }
+    Ok(Distribution { min, probabilities })
+}
+
+fn negate(a: &Distribution) -> Result<Distribution, Error> {
+    let min = a.max().checked_neg().ok_or(OverflowPositive)?;
+    let mut probabilities = a.probabilities.clone();
+    probabilities.reverse();
+    Ok(Distribution { min, probabilities })
+}
+
+/// Distribution of a single fresh roll of a die.
+fn roll_distribution(a: &DiceTerm) -> Result<Distribution, Error> {
+    match &a.faces {
+        Some(faces) => Distribution::uniform(faces.values().iter().copied()),
+        None => Distribution::uniform(1..=a.size),
+    }
+}
+
+/// Distribution of a single die's face, after rerolls.
+fn face_distribution(a: &DiceTerm) -> Result<Distribution, Error> {
+    let uniform = roll_distribution(a)?;
+    let reroll = match a.reroll {
+        Some(reroll) => reroll,
+        None => return Ok(uniform),
+    };
+    let condition = reroll.condition;
+    let mut faces = uniform.clone();
+    for _ in 0..reroll.limit() {
+        let rerolled = faces.filter(|x| condition.matches(x)).total();
+        faces = mix(faces.filter(|x| !condition.matches(x)), uniform.clone().scaled(rerolled))?;
+    }
+    Ok(faces.trimmed())
+}
+
+/// Distribution of a single die's value, including explosions.
+fn die_distribution(a: &DiceTerm, budget: &mut Budget) -> Result<Distribution, Error> {
+    let faces = face_distribution(a)?;
+    let explode = match a.explode {
+        Some(explode) => explode,
+        None => return Ok(faces),
+    };
+    let threshold = explode.threshold;
+    let penalty = match explode.kind {
+        crate::parse::ExplodeKind::Penetrating => 1,
+        _ => 0,
+    };
+    // Explosions are rolled fresh, without rerolls.
+    let mut explosion = roll_distribution(a)?;
+    explosion.min -= penalty;
+    let matches = |d: &Distribution| d.filter(|x| threshold.matches(x + penalty));
+    let misses = |d: &Distribution| d.filter(|x| !threshold.matches(x + penalty));
+    // `chain` is the sum of the extra rolls made once a die explodes,
+    // built up from the last explosion allowed.
+    let mut chain = explosion.clone();
+    for _ in 1..EXPLOSION_LIMIT {
+        chain = mix(misses(&explosion), convolve(&matches(&explosion), &chain, budget)?)?;
+    }
+    mix(
+        faces.filter(|x| !threshold.matches(x)),
+        convolve(&faces.filter(|x| threshold.matches(x)), &chain, budget)?,
+    )
+}
+
+/// Score of a die with value `x`, under a success counting target.
+fn score(target: Target, x: i64) -> i64 {
+    if target.success.matches(x) {
+        1
+    } else if matches!(target.failure, Some(f) if f.matches(x)) {
+        -1
+    } else {
+        0
+    }
+}
+
+/// Sum of `number` independent copies of `die`.
+fn repeat(die: &Distribution, number: i64, budget: &mut Budget) -> Result<Distribution, Error> {
+    checked_mul(die.max(), number)?;
+    checked_mul(die.min, number)?;
+    if die.probabilities.len() == 1 {
+        return Ok(Distribution::point(die.min * number));
+    }
+    let mut total = Distribution::point(0);
+    for _ in 0..number {
+        total = convolve(&total, die, budget)?;
+    }
+    Ok(total)
+}
+
+/// Sum of the scores of the dice kept from `number` independent copies of `die`.
+///
+/// Dice are assigned values one distinct value at a time, starting from
+/// the end whose dice are kept, tracking how many dice have been assigned so far.
+/// Each die not yet assigned takes the current value with its probability among
+/// the values left, so the count taking it is binomial. The first `kept` dice
+/// assigned are the ones kept, after which the rest no longer matter.
+fn keep_distribution<F>(
+    die: &Distribution,
+    number: i64,
+    keep: Keep,
+    score: F,
+    budget: &mut Budget,
+) -> Result<Distribution, Error>
+where
+    F: Fn(i64) -> i64,
+{
+    let (low, high) = keep.dropped(number);
+    let kept = number - low - high;
+    let mut values: Vec<_> = die.iter().collect();
+    if high == 0 && low > 0 {
+        // Keep the highest.
+        values.reverse();
+    }
+    if kept == 0 || values.is_empty() {
+        return Ok(Distribution::point(0));
+    }
+    // Refuse anything too big before allocating for it.
+    let least = values.iter().map(|&(x, _)| score(x)).min().unwrap_or(0);
+    let most = values.iter().map(|&(x, _)| score(x)).max().unwrap_or(0);
+    let lowest = checked_mul(least.min(0), kept)?;
+    let highest = checked_mul(most.max(0), kept)?;
+    width(lowest, highest)?;
+    let steps = (values.len() as u64)
+        .saturating_mul(kept as u64 + 1)
+        .saturating_mul(number as u64 + 1);
+    budget.spend(usize::try_from(steps).unwrap_or(usize::MAX))?;
+    let (kept, number) = (kept as usize, number as usize);
+    let mut ln_factorials = vec![0.0; number + 1];
+    for n in 1..=number {
+        ln_factorials[n] = ln_factorials[n - 1] + (n as f64).ln();
+    }
+    // Chance of the values left after each one, from each on.
+    let mut left: Vec<f64> = values
+        .iter()
+        .rev()
+        .scan(0.0, |total, (_, p)| {
+            *total += p;
+            Some(*total)
+        })
+        .collect();
+    left.reverse();
+    // `states[c]` is the distribution of the kept total, having assigned `c` dice.
+    let mut states: Vec<Option<Distribution>> = vec![None; kept];
+    states[0] = Some(Distribution::point(0));
+    let mut done = Distribution { min: 0, probabilities: Vec::new() };
+    for (i, &(value, p)) in values.iter().enumerate() {
+        let q = if i + 1 == values.len() { 1.0 } else { (p / left[i]).min(1.0) };
+        let mut next: Vec<Option<Distribution>> = vec![None; kept];
+        for (c, state) in states.iter().enumerate() {
+            let state = match state {
+                Some(x) => x,
+                None => continue,
+            };
+            let n = number - c;
+            // Chance of `j` of the `n` dice left taking this value.
+            let binomial = |j: usize| {
+                if q == 1.0 {
+                    return if j == n { 1.0 } else { 0.0 };
+                }
+                let ln_choose = ln_factorials[n] - ln_factorials[j] - ln_factorials[n - j];
+                (ln_choose + j as f64 * q.ln() + (n - j) as f64 * (-q).ln_1p()).exp()
+            };
+            let mut shifted = |j: usize, weight: f64| -> Result<Option<Distribution>, Error> {
+                if weight == 0.0 {
+                    return Ok(None);
+                }
+                budget.spend(state.probabilities.len())?;
+                let min = checked_add(state.min, score(value) * j as i64)?;
+                let probabilities = state.probabilities.clone();
+                Ok(Some(Distribution { min, probabilities }.scaled(weight)))
+            };
+            // Normalized, to cancel out rounding in the logarithms.
+            let below: Vec<f64> = (0..kept - c).map(binomial).collect();
+            let filled: f64 = (kept - c..=n).map(binomial).sum();
+            let total = filled + below.iter().sum::<f64>();
+            // Too few take this value to fill the dice kept.
+            for (j, weight) in below.into_iter().enumerate() {
+                if let Some(x) = shifted(j, weight / total)? {
+                    next[c + j] = Some(match next[c + j].take() {
+                        Some(y) => mix(y, x)?,
+                        None => x,
+                    });
+                }
+            }
+            if let Some(x) = shifted(kept - c, filled / total)? {
+                done = mix(done, x)?;
+            }
+        }
+        states = next;
+    }
+    Ok(done.trimmed())
+}
+
+fn dice_distribution(a: &DiceTerm, budget: &mut Budget) -> Result<Distribution, Error> {
+    let die = die_distribution(a, budget)?;
+    match (a.keep, a.target) {
+        (Some(keep), Some(target)) => keep_distribution(&die, a.number, keep, |x| score(target, x), budget),
+        (Some(keep), None) => keep_distribution(&die, a.number, keep, |x| x, budget),
+        (None, Some(target)) => {
+            let scores = [-1, 0, 1].iter().map(|&s| die.filter(|x| score(target, x) == s).total());
+            let scores = Distribution {
+                min: -1,
+                probabilities: scores.collect(),
+            };
+            repeat(&scores.trimmed(), a.number, budget)
+        }
+        (None, None) => repeat(&die, a.number, budget),
+    }
+}
+
+fn term_distribution(a: &Expr, budget: &mut Budget) -> Result<Distribution, Error> {
+    let d = match &a.term {
+        Term::Constant(x) => Distribution::point(*x),
+        Term::Dice(x) | Term::Percentile(x) => dice_distribution(x, budget)?,
+        Term::Fate(x) => repeat(&Distribution::uniform(-1..=1)?, x.number, budget)?,
+    };
+    match a.sign {
+        Sign::Positive => Ok(d),
+        Sign::Negative => negate(&d),
+    }
+}
+
+fn node_distribution(node: &Node, budget: &mut Budget) -> Result<Distribution, Error> {
+    match node {
+        Node::Term(x) => term_distribution(x, budget),
+        Node::Neg(x) => negate(&node_distribution(x, budget)?),
+        Node::Group(x) | Node::Floor(x) => node_distribution(x, budget),
+        Node::Sum(xs) => {
+            let mut total = Distribution::point(0);
+            for x in xs {
+                total = convolve(&total, &node_distribution(x, budget)?, budget)?;
+            }
+            Ok(total)
+        }
+        Node::Product(lhs, op, rhs) => {
+            let (lhs, rhs) = (node_distribution(lhs, budget)?, node_distribution(rhs, budget)?);
+            match op {
+                Operator::Multiply => combine(&lhs, &rhs, budget, checked_mul),
+                Operator::Divide => {
+                    if rhs.pmf(0) != 0.0 {
+                        return Err(DivisionByZero.into());
+                    }
+                    combine(&lhs, &rhs, budget, floor_div)
+                }
+            }
+        }
+    }
+}
+
+/// Compute the distribution of an expression.
+pub(crate) fn distribution(root: &Node) -> Result<Distribution, Error> {
+    node_distribution(root, &mut Budget(0))
+}
+
+/// Lowest and highest values a single die can take.
+fn die_bounds(a: &DiceTerm) -> (i128, i128) {
+    let (low, high) = match &a.faces {
+        Some(faces) => {
+            let values = faces.values().iter().copied();
+            (values.clone().min().unwrap_or(0), values.max().unwrap_or(0))
+        }
+        None => (1, a.size),
+    };
+    let (low, high) = (i128::from(low), i128::from(high));
+    match a.explode {
+        Some(explode) => {
+            let penalty = match explode.kind {
+                crate::parse::ExplodeKind::Penetrating => 1,
+                _ => 0,
+            };
+            let limit = i128::from(EXPLOSION_LIMIT);
+            // Faces passing a comparison are always a range of faces,
+            // so when both ends pass, every die explodes as far as it can.
+            let threshold = explode.threshold;
+            let always = threshold.matches(low as i64) && threshold.matches(high as i64);
+            // Otherwise, assume every extra roll is as extreme as it can be.
+            let least = if always { low - penalty } else { (low - penalty).min(0) };
+            (low + limit * least, high + limit * (high - penalty).max(0))
+        }
+        None => (low, high),
+    }
+}
+
+fn term_bounds(a: &Expr) -> (i128, i128) {
+    let (low, high) = match &a.term {
+        Term::Constant(x) => (i128::from(*x), i128::from(*x)),
+        Term::Dice(x) | Term::Percentile(x) => {
+            let kept = match x.keep {
+                Some(keep) => {
+                    let (low, high) = keep.dropped(x.number);
+                    x.number - low - high
+                }
+                None => x.number,
+            };
+            let kept = i128::from(kept);
+            match x.target {
+                Some(target) if target.failure.is_some() => (-kept, kept),
+                Some(_) => (0, kept),
+                None => {
+                    let (low, high) = die_bounds(x);
+                    (kept * low, kept * high)
+                }
+            }
+        }
+        Term::Fate(x) => (-i128::from(x.number), i128::from(x.number)),
+    };
+    match a.sign {
+        Sign::Positive => (low, high),
+        Sign::Negative => (-high, -low),
+    }
+}
+
+/// Like checked sums, this refuses `i64::MIN`, so that it can be negated.
+fn checked_bounds((low, high): (i128, i128)) -> Result<(i128, i128), Error> {
+    if high > i128::from(i64::MAX) {
+        Err(OverflowPositive.into())
+    } else if low <= i128::from(i64::MIN) {
+        Err(OverflowNegative.into())
+    } else {
+        Ok((low, high))
+    }
+}
+
+fn node_bounds(node: &Node) -> Result<(i128, i128), Error> {
+    checked_bounds(match node {
+        Node::Term(x) => term_bounds(x),
+        Node::Neg(x) => {
+            let (low, high) = node_bounds(x)?;
+            (-high, -low)
+        }
+        Node::Group(x) | Node::Floor(x) => node_bounds(x)?,
+        Node::Sum(xs) => {
+            let mut total = (0, 0);
+            for x in xs {
+                let (low, high) = node_bounds(x)?;
+                total = checked_bounds((total.0 + low, total.1 + high))?;
+            }
+            total
+        }
+        Node::Product(lhs, op, rhs) => {
+            let (lhs, rhs) = (node_bounds(lhs)?, node_bounds(rhs)?);
+            if let Operator::Divide = op {
+                if rhs.0 <= 0 && rhs.1 >= 0 {
+                    return Err(DivisionByZero.into());
+                }
+            }
+            // Both operations are monotonic in each operand, as long as
+            // divisors don't cross zero, so the extremes are at the corners.
+            let corners = [(lhs.0, rhs.0), (lhs.0, rhs.1), (lhs.1, rhs.0), (lhs.1, rhs.1)];
+            let values = corners.iter().map(|&(x, y)| match op {
+                Operator::Multiply => x * y,
+                Operator::Divide if x % y != 0 && (x < 0) != (y < 0) => x / y - 1,
+                Operator::Divide => x / y,
+            });
+            (values.clone().min().unwrap(), values.max().unwrap())
+        }
+    })
+}
+
+/// Lowest and highest totals an expression could possibly roll.
+///
+/// Fails if any part of the expression could overflow or divide by zero.
+pub(crate) fn bounds(root: &Node) -> Result<(i64, i64), Error> {
+    let (low, high) = node_bounds(root)?;
+    // Already checked to fit.
+    Ok((low as i64, high as i64))
+}
+
+/// Totals observed over many rolls of an expression.
+///
+/// For expressions whose exact `Distribution` is impractical to compute.
+/// See `builder::Roll::simulate`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Simulation {
+    counts: BTreeMap<i64, u64>,
+    trials: u64,
+}
+impl Simulation {
+    pub(crate) fn record(&mut self, total: i64) {
+        *self.counts.entry(total).or_insert(0) += 1;
+        self.trials += 1;
+    }
+    /// Number of rolls made.
+    pub fn trials(&self) -> u64 {
+        self.trials
+    }
+    /// Number of rolls that totalled `outcome`.
+    pub fn count(&self, outcome: i64) -> u64 {
+        self.counts.get(&outcome).copied().unwrap_or(0)
+    }
+    /// Every total observed with the number of rolls that gave it,
+    /// from lowest to highest.
+    pub fn histogram(&self) -> impl Iterator<Item = (i64, u64)> + '_ {
+        self.counts.iter().map(|(x, n)| (*x, *n))
+    }
+    pub fn mean(&self) -> f64 {
+        let sum: f64 = self.histogram().map(|(x, n)| x as f64 * n as f64).sum();
+        sum / self.trials as f64
+    }
+    pub fn std_dev(&self) -> f64 {
+        let mean = self.mean();
+        let sum: f64 = self.histogram().map(|(x, n)| (x as f64 - mean).powi(2) * n as f64).sum();
+        (sum / self.trials as f64).sqrt()
+    }
+    /// Lowest total that at least `percent` percent of rolls were at or below.
+    /// `None` if no rolls were made.
+    pub fn percentile(&self, percent: f64) -> Option<i64> {
+        let rank = (percent / 100.0 * self.trials as f64).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (x, n) in self.histogram() {
+            seen += n;
+            if seen >= rank {
+                return Some(x);
+            }
+        }
+        self.counts.keys().next_back().copied()
+    }
+}
@@ -9,15 +9,38 @@ pub enum Error {
     /// This indicates the usage of a die with <= 0 sides
     #[error("Invalid die")]
     InvalidDie,
+    /// A number in the expression is too large for an `i64`
+    #[error("number is too large")]
+    TooLarge,
+    /// Input was left over after a whole expression,
+    /// from `offset` bytes in, at the `column`th character
+    #[error("couldn't understand the input from column {column}")]
+    TrailingInput { offset: usize, column: usize },
     /// The sum of terms is greater than what an `i64` can hold
     #[error("sum is too high for `i64`")]
     OverflowPositive(#[from] crate::OverflowPositive),
     /// The sum of terms is lower than what an `i64` can hold
     #[error("sum is too low for `i64`")]
     OverflowNegative(#[from] crate::OverflowNegative),
+    /// A divisor in the expression evaluated to zero
+    #[error("division by zero")]
+    DivisionByZero(#[from] crate::DivisionByZero),
+    /// The expression has too many possible outcomes to analyze exactly
+    #[error("expression is too complex to analyze")]
+    TooComplex(#[from] crate::TooComplex),
     /// The expression evaluated isn't a valid dice expression
-    #[error("you've specified an invalid dice expression")]
-    InvalidExpression(#[from] ParseError),
+    #[error("{0}")]
+    InvalidExpression(ParseError),
+}
+impl From<ParseError> for Error {
+    fn from(e: ParseError) -> Self {
+        match e {
+            ParseError::InvalidDie { .. } => Error::InvalidDie,
+            ParseError::TooLarge { .. } => Error::TooLarge,
+            ParseError::TrailingInput { offset, column } => Error::TrailingInput { offset, column },
+            e => Error::InvalidExpression(e),
+        }
+    }
 }
 
 macro_rules! impl_zst_neg {
@@ -8,8 +8,20 @@ use crate::{
     parse::{wrap_dice, DiceTerm, Expr, Sign, Term, ParseError, InvalidDie},
     post::EResult,
 };
+use thiserror::Error;
 pub(crate) type ExprTuple = (i64, i64);
 
+/// Reasons an expression may fail to convert to tuples.
+#[derive(Debug, Clone, Copy, Error)]
+pub enum TupleError {
+    #[error(transparent)]
+    InvalidExpression(#[from] ParseError),
+    /// The expression is valid, but uses something
+    /// the tuple format has no way to express.
+    #[error("{0} can't be represented as tuples")]
+    Unrepresentable(&'static str),
+}
+
 impl TryFrom<ExprTuple> for Expr {
     type Error = InvalidDie;
     fn try_from(tup: ExprTuple) -> Result<Self, InvalidDie> {
@@ -27,19 +39,35 @@ impl TryFrom<ExprTuple> for Expr {
                 ::core::cmp::Ordering::Greater => return Err(InvalidDie),
             },
             sign,
+            label: None,
         })
     }
 }
-impl From<Expr> for ExprTuple {
-    fn from(e: Expr) -> ExprTuple {
+impl TryFrom<Expr> for ExprTuple {
+    type Error = TupleError;
+    fn try_from(e: Expr) -> Result<ExprTuple, TupleError> {
+        if e.label.is_some() {
+            return Err(TupleError::Unrepresentable("labeled terms"));
+        }
         let t = match e.term {
-            Term::Dice(x) => (x.number, x.size),
+            Term::Dice(x) | Term::Percentile(x) => {
+                if x.keep.is_some() || x.explode.is_some() || x.reroll.is_some() || x.target.is_some()
+                    || x.critical_success.is_some()
+                    || x.critical_failure.is_some() {
+                    return Err(TupleError::Unrepresentable("dice with modifiers"));
+                }
+                if x.faces.is_some() {
+                    return Err(TupleError::Unrepresentable("dice with custom faces"));
+                }
+                (x.number, x.size)
+            }
             Term::Constant(x) => (x, 1),
+            Term::Fate(_) => return Err(TupleError::Unrepresentable("Fate dice")),
         };
-        match e.sign {
+        Ok(match e.sign {
             Sign::Positive => t,
             Sign::Negative => (-t.0, t.1),
-        }
+        })
     }
 }
 
@@ -50,16 +78,22 @@ impl From<Expr> for ExprTuple {
 ///
 /// There is no guarantee of the order of terms.
 ///
-/// The only possible error here is `Error::InvalidExpression`.
+/// Besides invalid expressions, this fails on expressions using
+/// anything other than addition and subtraction of plain dice and constants.
+/// Percentile dice are converted to `(number, 100)`.
 /// Other errors may be encountered in this function's complement:
 /// `roll_tuples`.
-pub fn tuple_vec(input: &str) -> Result<Vec<ExprTuple>, ParseError> {
+pub fn tuple_vec(input: &str) -> Result<Vec<ExprTuple>, TupleError> {
     let e = wrap_dice(input)?;
-    Ok(e.into_iter().map(|x| x.into()).collect())
+    if !e.root().is_flat() {
+        return Err(TupleError::Unrepresentable("operators besides `+` and `-`"));
+    }
+    e.into_iter().map(ExprTuple::try_from).collect()
 }
 /// Roll and sum a slice of tuples, in the form
 /// provided by this function's complement: `tuple_vec`
 #[cfg(feature = "thread_rng")]
+#[allow(clippy::needless_question_mark)]
 pub fn roll_tuples(input: &[ExprTuple]) -> EResult {
     Ok(RollBuilder::new().with_tuples(input).map_err(|e| Error::from(ParseError::from(e)))?.into_roll().unwrap().roll()?)
 }
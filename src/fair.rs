@@ -0,0 +1,164 @@
+//! Provably fair rolling, by commitment and reveal.
+//!
+//! The server commits to a secret seed by publishing its hash,
+//! the player contributes a seed of their own, and each roll is
+//! derived from both, so neither side can choose its outcome alone.
+//! Once the server reveals its seed, anyone can check the results.
+//!
+//! ```
+//! # use mice::{builder::RollBuilder, fair::verify};
+//! let mut roll = RollBuilder::new().parse("1d20 + 5")?.into_committed_roll([7; 32])?;
+//! let commitment = roll.commitment(); // Published before the player picks a seed.
+//! let result = roll.roll("player's seed")?;
+//! assert_eq!(result.fairness().unwrap().commitment(), commitment);
+//! let server_seed = roll.reveal();
+//! assert!(verify(&result, &server_seed).is_ok());
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+use crate::builder::{replay, Replay};
+use crate::parse::Expression;
+use crate::post::{EResult, ExpressionResult};
+use sha2::{Digest, Sha256};
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+use thiserror::Error;
+
+/// The SHA-256 hash of a server seed, published before rolling.
+/// Displayed as lowercase hexadecimal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Commitment([u8; 32]);
+impl Commitment {
+    /// Commit to a server seed.
+    pub fn of(server_seed: &[u8; 32]) -> Self {
+        Commitment(Sha256::digest(server_seed).into())
+    }
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+impl From<[u8; 32]> for Commitment {
+    fn from(hash: [u8; 32]) -> Self {
+        Commitment(hash)
+    }
+}
+impl Display for Commitment {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        for byte in &self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+/// What a fair roll was derived from, besides the secret server seed.
+#[derive(Debug, Clone)]
+pub struct Fairness {
+    commitment: Commitment,
+    client_seed: Arc<str>,
+    nonce: u64,
+}
+impl Fairness {
+    /// The commitment the roll was made under. Check that this is
+    /// the one published before the client seed was chosen.
+    pub fn commitment(&self) -> Commitment {
+        self.commitment
+    }
+    pub fn client_seed(&self) -> &str {
+        &self.client_seed
+    }
+    /// How many rolls were made under the same commitment before this one.
+    pub fn nonce(&self) -> u64 {
+        self.nonce
+    }
+}
+
+/// The seed a roll is replayed from, derived from both parties' seeds.
+fn derive_seed(server_seed: &[u8; 32], nonce: u64, client_seed: &str) -> u64 {
+    let hash = Sha256::new()
+        .chain(server_seed)
+        .chain(nonce.to_le_bytes())
+        .chain(client_seed.as_bytes())
+        .finalize();
+    let mut seed = [0; 8];
+    seed.copy_from_slice(&hash[..8]);
+    u64::from_le_bytes(seed)
+}
+
+/// A roll committed to a secret server seed.
+/// Every result under the same commitment uses a new nonce,
+/// so the server seed only needs to be revealed once they're done.
+pub struct FairRoll {
+    expression: Arc<Expression>,
+    server_seed: [u8; 32],
+    commitment: Commitment,
+    nonce: u64,
+}
+impl FairRoll {
+    pub(crate) fn new(expression: Expression, server_seed: [u8; 32]) -> Self {
+        FairRoll {
+            expression: Arc::new(expression),
+            commitment: Commitment::of(&server_seed),
+            server_seed,
+            nonce: 0,
+        }
+    }
+    /// The commitment to publish before the player picks their seed.
+    pub fn commitment(&self) -> Commitment {
+        self.commitment
+    }
+    /// The nonce the next result will use.
+    pub fn nonce(&self) -> u64 {
+        self.nonce
+    }
+    /// Roll with the player's seed. The result records everything
+    /// needed to verify it, apart from the server seed.
+    pub fn roll(&mut self, client_seed: &str) -> EResult {
+        let nonce = self.nonce;
+        self.nonce += 1;
+        let seed = derive_seed(&self.server_seed, nonce, client_seed);
+        let result = replay(&Replay::shared(seed, self.expression.clone()))?;
+        Ok(result.with_fairness(Fairness {
+            commitment: self.commitment,
+            client_seed: client_seed.into(),
+            nonce,
+        }))
+    }
+    /// Give up the server seed, so results can be verified.
+    /// No more rolls can be made under this commitment.
+    pub fn reveal(self) -> [u8; 32] {
+        self.server_seed
+    }
+}
+
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq)]
+pub enum FairnessError {
+    #[error("result wasn't rolled under a commitment")]
+    Unrecorded,
+    #[error("revealed seed doesn't match the commitment")]
+    WrongSeed,
+    #[error("result doesn't match the seeds it was rolled with")]
+    Mismatch,
+}
+
+/// Check a result of a `FairRoll` against the server seed revealed for it,
+/// by rolling it again from both seeds.
+///
+/// This doesn't know which commitment was published;
+/// compare that to the one in `ExpressionResult::fairness` separately.
+pub fn verify(result: &ExpressionResult, server_seed: &[u8; 32]) -> Result<(), FairnessError> {
+    let (fairness, record) = match (result.fairness(), result.replay()) {
+        (Some(fairness), Some(record)) => (fairness, record),
+        _ => return Err(FairnessError::Unrecorded),
+    };
+    if Commitment::of(server_seed) != fairness.commitment {
+        return Err(FairnessError::WrongSeed);
+    }
+    let seed = derive_seed(server_seed, fairness.nonce, &fairness.client_seed);
+    if seed != record.seed() {
+        return Err(FairnessError::Mismatch);
+    }
+    match replay(record) {
+        Ok(x) if x.total() == result.total() && x.to_string() == result.to_string() => Ok(()),
+        _ => Err(FairnessError::Mismatch),
+    }
+}
@@ -24,22 +24,34 @@
 //! println!("{}", roll("9d8 + 4d2 - 5 - 8d7")?);
 //! # Ok::<(), Error>(())
 //! ```
+//!
+//! Terms may also be multiplied, divided, and grouped with parentheses.
+//! Division rounds down.
+//! ```
+//! # use mice::{roll, Error};
+//! println!("{}", roll("(2d6 + 3) * 2 - floor(3d6 / 2)")?);
+//! # Ok::<(), Error>(())
+//! ```
 #![forbid(unsafe_code)]
 use rand::Rng;
+use std::collections::BinaryHeap;
+use std::convert::TryFrom;
 mod error;
 pub use error::Error;
 use error::MyResult;
 mod post;
-use post::{EResult, EvaluatedTerm, RolledDie};
-pub use post::{ExpressionResult, FormatOptions};
+use post::{DieRoll, EResult, EvaluatedTerm, RolledDie};
+pub use post::{BatchResult, Critical, DieResult, ExpressionResult, FormatOptions, MultiResult, Outcome, TermResult, Transform};
 mod expose;
 #[cfg(feature = "thread_rng")]
 pub use expose::roll_tuples;
-pub use expose::tuple_vec;
+pub use expose::{tuple_vec, TupleError};
 pub mod parse;
-use parse::{DiceTerm, Expr, Sign, Term};
+use parse::{DiceTerm, ExplodeKind, FateTerm, Expr, Expression, Node, Operator, Term, EXPLOSION_LIMIT};
 pub use parse::ParseError;
 pub mod builder;
+pub mod distribution;
+pub mod fair;
 use builder::RollBuilder;
 mod display;
 pub mod prelude;
@@ -51,6 +63,12 @@ pub struct OverflowPositive;
 #[error("sum is too low for `i64`")]
 pub struct OverflowNegative;
 #[derive(::thiserror::Error, Debug, Clone, Copy)]
+#[error("division by zero")]
+pub struct DivisionByZero;
+#[derive(::thiserror::Error, Debug, Clone, Copy)]
+#[error("expression is too complex to analyze")]
+pub struct TooComplex;
+#[derive(::thiserror::Error, Debug, Clone, Copy)]
 enum Overflow {
     #[error(transparent)]
     Positive(#[from] OverflowPositive),
@@ -75,49 +93,293 @@ impl ::core::ops::Neg for Overflow {
         }
     }
 }
-fn roll_die_with<R>(a: &DiceTerm, rng: &mut R) -> Result<RolledDie, OverflowPositive>
+/// How the values of dice are summed within a term.
+///
+/// Sums are checked for overflow, unless the whole expression
+/// was already found to be safe, as by `builder::SafeRoll`.
+pub(crate) trait Summing {
+    fn add(a: i64, b: i64) -> Result<i64, Overflow>;
+}
+pub(crate) struct Checked;
+impl Summing for Checked {
+    /// Sums of exactly `i64::MIN` are refused too, so that they can be negated.
+    fn add(a: i64, b: i64) -> Result<i64, Overflow> {
+        match a.checked_add(b) {
+            Some(x) if x != i64::MIN => Ok(x),
+            _ => Err(overflow(b)),
+        }
+    }
+}
+pub(crate) struct Unchecked;
+impl Summing for Unchecked {
+    #[inline]
+    fn add(a: i64, b: i64) -> Result<i64, Overflow> {
+        Ok(a + b)
+    }
+}
+
+/// Overflow in the direction of `x`.
+fn overflow<T: Default + PartialOrd>(x: T) -> Overflow {
+    if x < T::default() {
+        OverflowNegative.into()
+    } else {
+        OverflowPositive.into()
+    }
+}
+
+fn roll_face<R>(a: &DiceTerm, rng: &mut R) -> i64
 where
     R: Rng,
 {
-    if a.size == 1 {
-        Ok(RolledDie {
-            total: a.number,
-            parts: (0..a.number).map(|_| 1).collect(),
-            sign_part: Sign::Positive,
-        })
+    if let Some(faces) = &a.faces {
+        let faces = faces.values();
+        // Drawn as a `u64`, so seeded rolls are the same on 32 bit platforms.
+        return faces[rng.gen_range(0, faces.len() as u64) as usize];
+    }
+    // Rng::gen_range has an exlusive upper bound
+    // Rng::gen includes the entire range of a type.
+    if let Some(bound) = a.size.checked_add(1) {
+        rng.gen_range(1, bound)
+    } else {
+        rng.gen()
+    }
+}
+
+/// Whether `face` is a critical on a die of `a`.
+/// A range written out wins over a default one where they overlap,
+/// so every face of `d6cf>=1` is a failure. Otherwise, successes win.
+fn critical(a: &DiceTerm, face: i64) -> Option<Critical> {
+    let success = matches!(a.critical_success(), Some(c) if c.matches(face));
+    let failure = matches!(a.critical_failure(), Some(c) if c.matches(face));
+    match (success, failure) {
+        (true, true) if a.critical_success.is_none() && a.critical_failure.is_some() => Some(Critical::Failure),
+        (true, _) => Some(Critical::Success),
+        (false, true) => Some(Critical::Failure),
+        (false, false) => None,
+    }
+}
+
+fn roll_die_with<S, R>(a: &DiceTerm, rng: &mut R) -> Result<RolledDie, Overflow>
+where
+    S: Summing,
+    R: Rng,
+{
+    if a.size == 1 && a.faces.is_none() && a.keep.is_none() && a.explode.is_none() && a.reroll.is_none() && a.target.is_none() && a.critical_success.is_none() && a.critical_failure.is_none() {
+        let parts = (0..a.number).map(|_| DieRoll::new(1)).collect();
+        Ok(RolledDie::new(a.number, parts, false, false))
     } else {
-        let mut total: i64 = 0;
         let mut parts = Vec::new();
-        // Rng::gen_range has an exlusive upper bound
-        // Rng::gen includes the entire range of a type.
         for _ in 0..a.number {
-            let random;
-            if let Some(bound) = a.size.checked_add(1) {
-                random = rng.gen_range(1, bound);
-            } else {
-                random = rng.gen();
+            let mut part = DieRoll::new(roll_face(a, rng));
+            if let Some(reroll) = a.reroll {
+                let mut count = 0;
+                while reroll.condition.matches(part.face) && count < reroll.limit() {
+                    part.rerolled.push(part.face);
+                    part.face = roll_face(a, rng);
+                    count += 1;
+                }
+                part.value = part.face;
+            }
+            part.critical = critical(a, part.face);
+            if let Some(explode) = a.explode {
+                let mut last = part.face;
+                let mut depth = 0;
+                while explode.threshold.matches(last) && depth < EXPLOSION_LIMIT {
+                    last = roll_face(a, rng);
+                    let extra = match explode.kind {
+                        ExplodeKind::Penetrating => last - 1,
+                        _ => last,
+                    };
+                    part.value = S::add(part.value, extra)?;
+                    part.chain.push(extra);
+                    depth += 1;
+                }
+                part.compounded = explode.kind == ExplodeKind::Compounding;
+            }
+            parts.push(part);
+        }
+        if let Some(keep) = a.keep {
+            let (low, high) = keep.dropped(a.number);
+            let mut ranked: Vec<usize> = (0..parts.len()).collect();
+            ranked.sort_by_key(|&i| parts[i].value);
+            let (dropped_low, rest) = ranked.split_at(low as usize);
+            let dropped_high = &rest[rest.len() - high as usize..];
+            for &i in dropped_low.iter().chain(dropped_high) {
+                parts[i].kept = false;
+            }
+        }
+        let mut total: i64 = 0;
+        if let Some(target) = a.target {
+            // Counts can't exceed the number of dice, so this can't overflow.
+            for part in parts.iter_mut().filter(|x| x.kept) {
+                if target.success.matches(part.value) {
+                    part.outcome = Some(Outcome::Success);
+                    total += 1;
+                } else if matches!(target.failure, Some(f) if f.matches(part.value)) {
+                    part.outcome = Some(Outcome::Failure);
+                    total -= 1;
+                } else {
+                    part.outcome = Some(Outcome::Neither);
+                }
+            }
+        } else {
+            for part in parts.iter().filter(|x| x.kept) {
+                total = S::add(total, part.value)?;
             }
-            total = total.checked_add(random).ok_or(OverflowPositive)?;
-            parts.push(random);
         }
-        Ok(RolledDie {
-            total,
-            parts,
-            sign_part: Sign::Positive,
-        })
+        Ok(RolledDie::new(total, parts, a.target.is_some(), false))
+    }
+}
+
+/// Roll a single die, following rerolls and explosions,
+/// without recording any of the faces it showed.
+/// Also gives the number of rolls it would be displayed with.
+fn roll_die_value<S, R>(a: &DiceTerm, rng: &mut R) -> Result<(i64, usize), Overflow>
+where
+    S: Summing,
+    R: Rng,
+{
+    let mut face = roll_face(a, rng);
+    let mut shown = 1;
+    if let Some(reroll) = a.reroll {
+        let mut count = 0;
+        while reroll.condition.matches(face) && count < reroll.limit() {
+            face = roll_face(a, rng);
+            count += 1;
+        }
+        shown += count as usize;
+    }
+    let mut value = face;
+    if let Some(explode) = a.explode {
+        let mut last = face;
+        let mut depth = 0;
+        while explode.threshold.matches(last) && depth < EXPLOSION_LIMIT {
+            last = roll_face(a, rng);
+            let extra = match explode.kind {
+                ExplodeKind::Penetrating => last - 1,
+                _ => last,
+            };
+            value = S::add(value, extra)?;
+            depth += 1;
+        }
+        if explode.kind != ExplodeKind::Compounding {
+            shown += depth as usize;
+        }
+    }
+    Ok((value, shown))
+}
+
+/// Like `roll_die_with`, but without keeping the dice rolled.
+///
+/// This runs in constant memory, except when keeping or dropping dice,
+/// where it remembers the fewer of the kept dice and the dropped dice.
+fn roll_die_slim<S, R>(a: &DiceTerm, rng: &mut R) -> Result<RolledDie, Overflow>
+where
+    S: Summing,
+    R: Rng,
+{
+    let counting = a.target.is_some();
+    if a.size == 1 && a.faces.is_none() && a.keep.is_none() && a.explode.is_none() && a.reroll.is_none() && !counting && a.critical_success.is_none() && a.critical_failure.is_none() {
+        return Ok(RolledDie::slim(a.number, a.number as usize, false, false));
     }
+    let score = |value: i64| match a.target {
+        Some(target) if target.success.matches(value) => 1,
+        Some(target) if matches!(target.failure, Some(f) if f.matches(value)) => -1,
+        Some(_) => 0,
+        None => value,
+    };
+    let mut shown = 0;
+    let total = match a.keep {
+        Some(keep) => {
+            // Only one end is ever dropped, so the kept dice are
+            // either the highest or the lowest of the lot.
+            let (low, high) = keep.dropped(a.number);
+            let kept = a.number - low - high;
+            let dropped = low + high;
+            let (limit, highest) = if kept <= dropped {
+                (kept, high == 0)
+            } else {
+                (dropped, high != 0)
+            };
+            // The `limit` highest dice seen so far, or lowest, keyed
+            // so that the least extreme is at the top of the heap.
+            let key = |value: i64| if highest { -value } else { value };
+            let mut remembered = BinaryHeap::new();
+            let mut everything: i128 = 0;
+            for _ in 0..a.number {
+                let (value, count) = roll_die_value::<S, _>(a, rng)?;
+                shown += count;
+                everything += i128::from(score(value));
+                remembered.push(key(value));
+                if remembered.len() > limit as usize {
+                    remembered.pop();
+                }
+            }
+            let remembered: i128 = remembered.into_iter().map(|x| i128::from(score(key(x)))).sum();
+            let total = if kept <= dropped { remembered } else { everything - remembered };
+            match i64::try_from(total) {
+                Ok(x) if x != i64::MIN => x,
+                _ => return Err(overflow(total)),
+            }
+        }
+        None => {
+            let mut total: i64 = 0;
+            for _ in 0..a.number {
+                let (value, count) = roll_die_value::<S, _>(a, rng)?;
+                shown += count;
+                total = S::add(total, score(value))?;
+            }
+            total
+        }
+    };
+    Ok(RolledDie::slim(total, shown, counting, false))
+}
+
+fn roll_fate_slim<R>(a: &FateTerm, rng: &mut R) -> RolledDie
+where
+    R: Rng,
+{
+    // Bounded by the number of dice, so this can't overflow.
+    let total = (0..a.number).map(|_| rng.gen_range(-1, 2)).sum();
+    RolledDie::slim(total, a.number as usize, false, true)
+}
+
+/// Like `eval_term_with`, but without keeping the dice rolled.
+fn eval_term_slim<S, R>(a: &Expr, rng: &mut R) -> Result<EvaluatedTerm, Overflow>
+where
+    S: Summing,
+    R: Rng,
+{
+    let t: MyResult<_, Overflow> = match &a.term {
+        Term::Dice(x) | Term::Percentile(x) => roll_die_slim::<S, _>(x, rng).into(),
+        Term::Constant(x) => MyResult::Ok(EvaluatedTerm::Constant(*x)),
+        Term::Fate(x) => MyResult::Ok(roll_fate_slim(x, rng).into()),
+    };
+    (a.sign * t).into()
+}
+
+fn roll_fate_with<R>(a: &FateTerm, rng: &mut R) -> RolledDie
+where
+    R: Rng,
+{
+    let parts: Vec<_> = (0..a.number).map(|_| DieRoll::new(rng.gen_range(-1, 2))).collect();
+    // Bounded by the number of dice, so this can't overflow.
+    let total = parts.iter().map(|x| x.value).sum();
+    RolledDie::new(total, parts, false, true)
 }
 
-fn eval_term_with<R>(a: &Expr, rng: &mut R) -> Result<EvaluatedTerm, Overflow>
+fn eval_term_with<S, R>(a: &Expr, rng: &mut R) -> Result<EvaluatedTerm, Overflow>
 where
+    S: Summing,
     R: Rng,
 {
-    let t: MyResult<_, Overflow> = match a.term {
-        Term::Dice(x) => roll_die_with(&x, rng).into(),
-        Term::Constant(x) => MyResult::Ok(EvaluatedTerm::Constant(x)),
+    let t: MyResult<_, Overflow> = match &a.term {
+        Term::Dice(x) | Term::Percentile(x) => roll_die_with::<S, _>(x, rng).into(),
+        Term::Constant(x) => MyResult::Ok(EvaluatedTerm::Constant(*x)),
+        Term::Fate(x) => MyResult::Ok(roll_fate_with(x, rng).into()),
     };
-    // No positive number can overflow via negation.
-    // Since terms are purely positive, this will never overflow.
+    // Dice totals are never `i64::MIN`, and neither are constants
+    // or Fate dice totals, so this will never overflow.
     (a.sign * t).into()
 }
 
@@ -136,44 +398,135 @@ where
 ///   - A d0 is used
 ///   - The sum of all terms is too high
 ///   - The sum of all terms is too low
+///   - Division by zero
 ///   - Nonsense input
 #[cfg(feature = "thread_rng")]
+#[allow(clippy::needless_question_mark)]
 pub fn roll(input: &str) -> EResult {
     Ok(RollBuilder::new().parse(input)?.into_roll().unwrap().roll()?)
 }
 
-fn try_roll_expr_iter_with<I, R>(rng: &mut R, input: I) -> EResult
+/// Roll several expressions one after another, with the same RNG,
+/// as for an attack's to-hit and damage.
+pub fn roll_list_with<R>(rng: &mut R, expressions: &[Expression]) -> Result<MultiResult, Error>
+where
+    R: Rng,
+{
+    let results = expressions
+        .iter()
+        .map(|x| roll_expression_with::<Checked, _>(rng, x))
+        .collect::<Result<_, _>>()?;
+    Ok(MultiResult::new(results))
+}
+
+/// Evaluate several dice expressions, separated by `;` or `,`.
+/// ```
+/// # use mice::{roll_list, Error, FormatOptions};
+/// let attack = roll_list("1d20 + 5; 2d6 + 3")?;
+/// let (to_hit, damage) = (&attack.results()[0], &attack.results()[1]);
+/// assert!((6..=25).contains(&to_hit.total()) && (5..=15).contains(&damage.total()));
+/// println!("{}", attack.format(FormatOptions::new().total_right()));
+/// # Ok::<(), Error>(())
+/// ```
+#[cfg(feature = "thread_rng")]
+pub fn roll_list(input: &str) -> Result<MultiResult, Error> {
+    let expressions = parse::wrap_dice_list(input)?;
+    roll_list_with(&mut rand::thread_rng(), &expressions)
+}
+
+/// Roll every term of an expression, and combine them.
+fn roll_expression_with<S, R>(rng: &mut R, expression: &Expression) -> EResult
 where
-    I: Iterator<Item = Result<Expr, Error>>,
+    S: Summing,
     R: Rng,
 {
     // let mut rng = thread_rng(); // This doesn't work in WASM?
-    let mut pairs = Vec::new();
-    let mut total: i64 = 0;
-    for x in input {
-        match x {
-            Ok(x) => {
-                let res = eval_term_with(&x, rng)?;
-                let res_val = res.value();
-                pairs.push((x, res));
-                total = total.checked_add(res_val).ok_or(if res_val > 0 {
-                    Error::OverflowPositive(OverflowPositive)
-                } else {
-                    Error::OverflowNegative(OverflowNegative)
-                })?;
-            }
-            Err(x) => return Err(x),
-        }
-    }
-    Ok(ExpressionResult::new(pairs, total))
+    let tree = expression
+        .root()
+        .try_map(&mut |x: &Expr| eval_term_with::<S, _>(x, rng).map(|res| (x.clone(), res)))?;
+    let total = eval_node(&tree, &mut |(_, res): &(Expr, EvaluatedTerm)| Ok(res.value()))?;
+    Ok(ExpressionResult::new(tree, total, expression.label.clone()))
 }
 
-fn roll_expr_iter_with<I, R>(rng: &mut R, input: I) -> EResult
+/// Roll an expression, keeping nothing but its total.
+///
+/// This draws from `rng` exactly as `roll_expression_with` does,
+/// so both give the same total for the same generator state.
+pub(crate) fn roll_total_with<S, R>(rng: &mut R, expression: &Expression) -> Result<i64, Error>
 where
-    I: Iterator<Item = Expr>,
+    S: Summing,
     R: Rng,
 {
-    try_roll_expr_iter_with(rng, input.map(Ok))
+    eval_node(expression.root(), &mut |x: &Expr| Ok(eval_term_slim::<S, _>(x, rng)?.value()))
+}
+
+/// Roll an expression, keeping only term totals
+/// rather than every die rolled.
+pub(crate) fn roll_slim_with<S, R>(rng: &mut R, expression: &Expression) -> EResult
+where
+    S: Summing,
+    R: Rng,
+{
+    let tree = expression
+        .root()
+        .try_map(&mut |x: &Expr| eval_term_slim::<S, _>(x, rng).map(|res| (x.clone(), res)))?;
+    let total = eval_node(&tree, &mut |(_, res): &(Expr, EvaluatedTerm)| Ok(res.value()))?;
+    Ok(ExpressionResult::new(tree, total, expression.label.clone()))
+}
+
+/// Combine the values of terms in an expression tree,
+/// checking for overflow and division by zero at every step.
+///
+/// Terms are visited from left to right.
+fn eval_node<T, F>(node: &Node<T>, value: &mut F) -> Result<i64, Error>
+where
+    F: FnMut(&T) -> Result<i64, Error>,
+{
+    match node {
+        Node::Term(x) => value(x),
+        Node::Neg(x) => eval_node(x, value)?
+            .checked_neg()
+            .ok_or(Error::OverflowPositive(OverflowPositive)),
+        Node::Group(x) | Node::Floor(x) => eval_node(x, value),
+        Node::Sum(xs) => {
+            let mut total: i64 = 0;
+            for x in xs {
+                total = checked_add(total, eval_node(x, value)?)?;
+            }
+            Ok(total)
+        }
+        Node::Product(lhs, op, rhs) => {
+            let (lhs, rhs) = (eval_node(lhs, value)?, eval_node(rhs, value)?);
+            match op {
+                Operator::Multiply => checked_mul(lhs, rhs),
+                Operator::Divide => floor_div(lhs, rhs),
+            }
+        }
+    }
+}
+
+/// Add with the same overflow errors as evaluating an expression.
+fn checked_add(a: i64, b: i64) -> Result<i64, Error> {
+    a.checked_add(b).ok_or_else(|| overflow(b).into())
+}
+
+/// Multiply with the same overflow errors as evaluating an expression.
+fn checked_mul(a: i64, b: i64) -> Result<i64, Error> {
+    a.checked_mul(b).ok_or_else(|| overflow(a.signum() * b.signum()).into())
+}
+
+/// Division rounding towards negative infinity.
+fn floor_div(lhs: i64, rhs: i64) -> Result<i64, Error> {
+    if rhs == 0 {
+        return Err(DivisionByZero.into());
+    }
+    // The only other way for this to fail is `i64::MIN / -1`.
+    let quotient = lhs.checked_div(rhs).ok_or(OverflowPositive)?;
+    if lhs % rhs != 0 && (lhs < 0) != (rhs < 0) {
+        Ok(quotient - 1)
+    } else {
+        Ok(quotient)
+    }
 }
 
 // N
@@ -183,17 +536,660 @@ where
 
 #[cfg(test)]
 mod tests {
-    use crate::{roll, DiceTerm};
+    use crate::{post::EvaluatedTerm, roll, DiceTerm, Error, Term};
     #[test]
     fn arithmetic() {
         assert_eq!(roll("5 + 3").unwrap().total(), 8);
         assert_eq!(roll("5 - 3").unwrap().total(), 2);
     }
     #[test]
+    #[allow(clippy::single_match)]
     fn dice() {
         match DiceTerm::new(0, 0) {
             Ok(_) => panic!(),
             Err(_) => (),
         }
     }
+    #[test]
+    fn operators() {
+        assert_eq!(roll("2 + 3 * 4").unwrap().total(), 14);
+        assert_eq!(roll("(2 + 3) * 4").unwrap().total(), 20);
+        assert_eq!(roll("10 - 4 - 3").unwrap().total(), 3);
+        assert_eq!(roll("10 - 2 * 3 + 1").unwrap().total(), 5);
+        assert_eq!(roll("7 / 2").unwrap().total(), 3);
+        assert_eq!(roll("-7 / 2").unwrap().total(), -4);
+        assert_eq!(roll("-(7 / 2)").unwrap().total(), -3);
+        assert_eq!(roll("floor(7 / 2) * -2").unwrap().total(), -6);
+        assert_eq!(roll("( 3d1 + 1 )*2").unwrap().total(), 8);
+        assert_eq!(roll("-(2 - 5)").unwrap().total(), 3);
+        assert!(matches!(roll("1 / (2 - 2)"), Err(Error::DivisionByZero(_))));
+        assert!(matches!(roll("9223372036854775807 * 2"), Err(Error::OverflowPositive(_))));
+        assert!(matches!(roll("9223372036854775807 * -2"), Err(Error::OverflowNegative(_))));
+        assert!(roll("(2 + 3").is_err());
+        assert!(roll("2 * * 3").is_err());
+        assert!(roll("2 + -3").is_err());
+        assert_eq!(roll("2 * -(1 + 2)").unwrap().total(), -6);
+    }
+    #[test]
+    fn nesting() {
+        use crate::parse::{wrap_dice, Expression, ParseError, NESTING_LIMIT};
+        let nested = |n| format!("{}1d6{}", "(".repeat(n), ")".repeat(n));
+        let deepest: Expression = nested(NESTING_LIMIT).parse().unwrap();
+        assert_eq!(deepest.to_string(), nested(NESTING_LIMIT));
+        assert!(deepest.roll().is_ok());
+        assert_eq!(deepest.distribution().unwrap().max(), 6);
+        assert_eq!(deepest.simplify(), deepest);
+        let too_deep = wrap_dice(&nested(NESTING_LIMIT + 1));
+        assert_eq!(too_deep.err(), Some(ParseError::TooDeep { offset: NESTING_LIMIT }));
+        assert!(matches!(roll(&nested(100_000)), Err(Error::InvalidExpression(ParseError::TooDeep { .. }))));
+        let chain = |n| format!("2{}", " * 1".repeat(n));
+        assert_eq!(roll(&chain(NESTING_LIMIT)).unwrap().total(), 2);
+        let too_long = wrap_dice(&chain(NESTING_LIMIT + 1));
+        assert_eq!(too_long.err(), Some(ParseError::TooDeep { offset: 2 + 4 * NESTING_LIMIT }));
+    }
+    #[test]
+    fn fate_and_percentile() {
+        for _ in 0..20 {
+            let total = roll("4dF").unwrap().total();
+            assert!((-4..=4).contains(&total));
+            let total = roll("-dF").unwrap().total();
+            assert!((-1..=1).contains(&total));
+            let total = roll("d%").unwrap().total();
+            assert!((1..=100).contains(&total));
+        }
+        assert_eq!(roll("3d%>=1").unwrap().total(), 3);
+        assert!(roll("0dF").is_ok());
+    }
+    #[test]
+    fn custom_faces() {
+        use crate::{builder::RollBuilder, parse::{Expression, NamedDice}};
+        for _ in 0..20 {
+            let total = roll("3d{1,1,2,3,5,8}").unwrap().total();
+            assert!((3..=24).contains(&total));
+        }
+        assert_eq!(roll("4d{ 2 , 2 }").unwrap().total(), 8);
+        assert_eq!(roll("3d{0,7}kh1>=0").unwrap().total(), 1);
+        for _ in 0..20 {
+            let total = roll("4d{-1, 0, +1}").unwrap().total();
+            assert!((-4..=4).contains(&total));
+        }
+        let expression = |x: &str| x.parse::<Expression>().unwrap();
+        let (fudge, fate) = (expression("4d{-1,0,1}").distribution().unwrap(), expression("4dF").distribution().unwrap());
+        assert_eq!((fudge.min(), fudge.max()), (-4, 4));
+        assert!(fudge.iter().zip(fate.iter()).all(|((a, p), (b, q))| a == b && (p - q).abs() < 1e-12));
+        assert_eq!(expression("2d{-3,-1}").bounds().unwrap(), (-6, -2));
+        assert_eq!(expression("d{-1, 0, 1}").to_string(), "1d{-1,0,1}");
+        assert_eq!(roll("-2d{-1}").unwrap().total(), 2);
+        assert!(matches!(roll("2d{-9223372036854775807}"), Err(Error::OverflowNegative(_))));
+        assert!(roll("d{}").is_err());
+        assert!(roll("dBoost").is_err());
+        let mut named = NamedDice::new();
+        assert!(named.define("F", &[1]).is_err());
+        assert!(named.define("B2", &[1]).is_err());
+        assert!(named.define("Boost", &[]).is_err());
+        let mut roll = RollBuilder::new()
+            .define_die("Boost", &[0, 0, 1, 1, 2, 2])
+            .unwrap()
+            .define_die("Boo", &[5])
+            .unwrap()
+            .parse("4dBoostkh2 + dBoo")
+            .unwrap()
+            .into_roll()
+            .unwrap();
+        for _ in 0..20 {
+            let total = roll.roll().unwrap().total();
+            assert!((5..=9).contains(&total));
+        }
+    }
+    #[test]
+    fn tuples() {
+        use crate::{tuple_vec, TupleError};
+        assert_eq!(tuple_vec("2d6 - 3 + d%").unwrap(), vec![(2, 6), (-3, 1), (1, 100)]);
+        assert!(matches!(tuple_vec("4dF"), Err(TupleError::Unrepresentable(_))));
+        assert!(matches!(tuple_vec("4d6kh3"), Err(TupleError::Unrepresentable(_))));
+        assert!(matches!(tuple_vec("4d{1,2}"), Err(TupleError::Unrepresentable(_))));
+        assert!(matches!(tuple_vec("2d6 * 2"), Err(TupleError::Unrepresentable(_))));
+        assert!(matches!(tuple_vec("2d0"), Err(TupleError::InvalidExpression(_))));
+    }
+    #[test]
+    fn keep_and_drop() {
+        for (input, kept) in &[("4d6kh3", 3), ("4d6kl1", 1), ("4d6dh1", 3), ("4d6dl3", 1), ("2d6kh5", 2)] {
+            let result = roll(input).unwrap();
+            match &result.tree().leaves()[0].1 {
+                EvaluatedTerm::Die(d) => {
+                    assert_eq!(d.parts.iter().filter(|x| x.kept).count(), *kept);
+                    assert_eq!(d.parts.iter().filter(|x| x.kept).map(|x| x.value).sum::<i64>(), d.total);
+                }
+                _ => panic!(),
+            }
+        }
+        assert!(roll("4d6kh3kh3").is_err());
+    }
+    #[test]
+    fn rerolling() {
+        // Rerolling ones on a d1 only stops at the limit.
+        let result = roll("2d1r").unwrap();
+        match &result.tree().leaves()[0].1 {
+            EvaluatedTerm::Die(d) => {
+                for part in &d.parts {
+                    assert_eq!(part.rerolled.len() as i64, crate::parse::REROLL_LIMIT);
+                }
+            }
+            _ => panic!(),
+        }
+        let result = roll("20d6r<3").unwrap();
+        match &result.tree().leaves()[0].1 {
+            EvaluatedTerm::Die(d) => {
+                for part in &d.parts {
+                    assert!(part.face >= 3);
+                    assert!(part.rerolled.iter().all(|&x| x < 3));
+                }
+            }
+            _ => panic!(),
+        }
+        let result = roll("20d6ro1").unwrap();
+        match &result.tree().leaves()[0].1 {
+            EvaluatedTerm::Die(d) => assert!(d.parts.iter().all(|x| x.rerolled.len() <= 1)),
+            _ => panic!(),
+        }
+    }
+    #[test]
+    fn success_counting() {
+        assert_eq!(roll("10d1>=1").unwrap().total(), 10);
+        assert_eq!(roll("10d1>1").unwrap().total(), 0);
+        assert_eq!(roll("10d1>1f1").unwrap().total(), -10);
+        assert_eq!(roll("10d1=1 + 3").unwrap().total(), 13);
+        let result = roll("20d10>=8f1").unwrap();
+        match &result.tree().leaves()[0].1 {
+            EvaluatedTerm::Die(d) => {
+                let successes = d.parts.iter().filter(|x| x.value >= 8).count() as i64;
+                let failures = d.parts.iter().filter(|x| x.value == 1).count() as i64;
+                assert_eq!(d.total, successes - failures);
+            }
+            _ => panic!(),
+        }
+        assert!(roll("10d10 10").is_err());
+    }
+    #[test]
+    fn exploding() {
+        use crate::parse::EXPLOSION_LIMIT;
+        let result = roll("3d1!").unwrap();
+        assert_eq!(result.total(), 3 * (EXPLOSION_LIMIT + 1));
+        let result = roll("3d1!p").unwrap();
+        assert_eq!(result.total(), 3);
+        let result = roll("2d6!>5").unwrap();
+        match &result.tree().leaves()[0].1 {
+            EvaluatedTerm::Die(d) => {
+                for part in &d.parts {
+                    assert_eq!(part.face + part.chain.iter().sum::<i64>(), part.value);
+                    let mut rolls = Some(&part.face).into_iter().chain(&part.chain);
+                    assert!(rolls.all(|&x| (1..=6).contains(&x)));
+                }
+            }
+            _ => panic!(),
+        }
+    }
+    #[test]
+    fn distributions() {
+        use crate::parse::Expression;
+        let distribution = |input| Expression::parse(input).unwrap().1.unwrap().distribution();
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-9;
+        let d = distribution("2d6").unwrap();
+        assert_eq!((d.min(), d.max()), (2, 12));
+        assert!(close(d.pmf(7), 1.0 / 6.0));
+        assert!(close(d.cdf(4), 6.0 / 36.0));
+        assert!(close(d.mean(), 7.0));
+        assert!(close(d.variance(), 35.0 / 6.0));
+        assert!(close(distribution("4d6kh3").unwrap().mean(), 15869.0 / 1296.0));
+        assert!(close(distribution("4d6dl1").unwrap().mean(), 15869.0 / 1296.0));
+        assert!(close(distribution("4d6kl1").unwrap().mean(), 2275.0 / 1296.0));
+        assert!(close(distribution("3d6kh5").unwrap().mean(), 10.5));
+        let d = distribution("2000d6kh1").unwrap();
+        assert_eq!(d.max(), 6);
+        assert!(close(d.mean(), 6.0));
+        assert!(close(distribution("100000d6kl1").unwrap().pmf(1), 1.0));
+        assert!(close(distribution("50d6kh49").unwrap().mean(), 175.0 - (1..=6).map(|k| (k as f64 / 6.0).powi(50)).sum::<f64>()));
+        assert!(matches!(distribution("100000d6kh50000"), Err(Error::TooComplex(_))));
+        assert!(matches!(distribution("9223372036854775807d6kh1"), Err(Error::TooComplex(_))));
+        assert!(close(distribution("d20ro1").unwrap().pmf(1), 1.0 / 400.0));
+        assert!(close(distribution("1d6!").unwrap().mean(), 4.2));
+        assert_eq!(distribution("3d1!").unwrap().min(), 63);
+        assert_eq!(distribution("3d1!p").unwrap().max(), 3);
+        assert!(close(distribution("10d10>=8").unwrap().mean(), 3.0));
+        assert!(close(distribution("4dF").unwrap().variance(), 8.0 / 3.0));
+        let d = distribution("-7 / 2 + d{2,2,5}").unwrap();
+        assert!(close(d.pmf(-2), 2.0 / 3.0));
+        let d = distribution("(2d6!r1 + 3) * 2 - 4d6kh3>4 / 2").unwrap();
+        assert!(close(d.iter().map(|(_, p)| p).sum(), 1.0));
+        let (_, a) = Expression::parse("2d6 + 3").unwrap();
+        let (_, b) = Expression::parse("1d12 + 2").unwrap();
+        let (a, b) = (a.unwrap(), b.unwrap());
+        assert!(close(a.chance_at_least(10).unwrap(), 21.0 / 36.0));
+        assert!(close(a.chance_at_most(5).unwrap(), 1.0 / 36.0));
+        assert!(close(a.chance_exactly(10).unwrap(), 1.0 / 6.0));
+        let greater = a.chance_greater_than(&b).unwrap();
+        let less = b.chance_greater_than(&a).unwrap();
+        let equal = a.distribution().unwrap().chance_equal(&b.distribution().unwrap());
+        assert!(close(greater, 7.0 / 12.0));
+        assert!(close(greater + less + equal, 1.0));
+        assert!(matches!(distribution("9223372036854775807 + 1d2"), Err(Error::OverflowPositive(_))));
+        assert!(matches!(distribution("1 / (1d2 - 1)"), Err(Error::DivisionByZero(_))));
+        assert!(matches!(distribution("d9223372036854775807"), Err(Error::TooComplex(_))));
+        assert!(matches!(distribution("d9000 * d9000"), Err(Error::TooComplex(_))));
+        let d = distribution("d{-3,-1,2} / d{-2,1,4}").unwrap();
+        assert_eq!((d.min(), d.max()), (-3, 2));
+    }
+    #[test]
+    fn simulation() {
+        use crate::builder::RollBuilder;
+        use crate::parse::Expression;
+        use rand::{rngs::StdRng, SeedableRng};
+        let simulation = RollBuilder::new().parse("2d6").unwrap().into_roll().unwrap().simulate(10_000).unwrap();
+        assert_eq!(simulation.histogram().map(|(_, n)| n).sum::<u64>(), 10_000);
+        assert!((simulation.mean() - 7.0).abs() < 0.2);
+        assert!((simulation.std_dev() - (35.0f64 / 6.0).sqrt()).abs() < 0.2);
+        assert_eq!(simulation.percentile(0.0), Some(2));
+        assert_eq!(simulation.percentile(100.0), Some(12));
+        // Totals alone come out the same as full rolls.
+        let input = "4d6kh3 - 2d10!>9r1 * 3dF + 5d{0,1,2}>=2f0 / (d%ro<5 + 1)";
+        let (_, expression) = Expression::parse(input).unwrap();
+        let expression = expression.unwrap();
+        let (mut a, mut b) = (StdRng::seed_from_u64(7), StdRng::seed_from_u64(7));
+        for _ in 0..100 {
+            let total = crate::roll_total_with::<crate::Checked, _>(&mut a, &expression).unwrap();
+            assert_eq!(total, expression.roll_with(&mut b).unwrap().total());
+        }
+    }
+    #[test]
+    fn slim_rolls() {
+        use crate::{builder::RollBuilder, FormatOptions};
+        use rand::{rngs::StdRng, SeedableRng};
+        let inputs = [
+            "d20", "d6!", "d6ro<4", "d1", "3d1", "4d6kh3", "4d6kl3", "5d6dh1", "9d6dl7",
+            "10d10>=8f1", "6d10kh2>7", "4dF", "-2d6 + 3 * d4", "d%", "0d6", "3d1cs1 + 1d20",
+        ];
+        let formats = [
+            FormatOptions::new().concise(),
+            FormatOptions::new().concise().total_right(),
+            FormatOptions::new().concise().total_left().term_commas(),
+        ];
+        for input in inputs.iter() {
+            for &format in formats.iter() {
+                let roll = |seed| {
+                    let builder = RollBuilder::new().parse(input).unwrap();
+                    builder.with_rng(StdRng::seed_from_u64(seed)).into_roll().unwrap()
+                };
+                for seed in 0..20 {
+                    let full = roll(seed).roll().unwrap();
+                    let slim = roll(seed).slim_roll(format).unwrap();
+                    assert_eq!(full.format(format), slim.format(format));
+                }
+            }
+        }
+    }
+    #[test]
+    fn safe_rolls() {
+        use crate::builder::{RollBuilder, SafeRoll};
+        use crate::parse::Expression;
+        let bounds = |input| Expression::parse(input).unwrap().1.unwrap().bounds();
+        assert_eq!(bounds("4d6kh3").unwrap(), (3, 18));
+        assert_eq!(bounds("3d1!").unwrap(), (63, 63));
+        assert_eq!(bounds("10d10>=8f1 - 4dF").unwrap(), (-14, 14));
+        assert_eq!(bounds("-7 / 2d2").unwrap(), (-4, -2));
+        assert!(matches!(bounds("9223372036854775807 + d2"), Err(Error::OverflowPositive(_))));
+        assert!(matches!(bounds("d9223372036854775807 * -2"), Err(Error::OverflowNegative(_))));
+        assert!(matches!(bounds("1 / (d2 - 1)"), Err(Error::DivisionByZero(_))));
+        // Bounds are never tighter than the outcomes that can actually happen.
+        for input in ["2d6!p * d{0,1,3}ro0", "(4d6dl1 - 3) / d4", "6d10kh2>7f1 + d%"].iter() {
+            let (_, expression) = Expression::parse(input).unwrap();
+            let expression = expression.unwrap();
+            let (low, high) = expression.bounds().unwrap();
+            let distribution = expression.distribution().unwrap();
+            assert!(low <= distribution.min() && distribution.max() <= high);
+            let roll = RollBuilder::new().parse(input).unwrap().into_roll().unwrap();
+            let mut roll = SafeRoll::new(roll).unwrap();
+            for _ in 0..50 {
+                assert!((low..=high).contains(&roll.total()));
+                assert!((low..=high).contains(&roll.roll().total()));
+            }
+        }
+        let roll = RollBuilder::new().parse("9223372036854775807 + d2").unwrap().into_roll().unwrap();
+        assert!(SafeRoll::new(roll).is_err());
+        // Whatever a checked roll refuses, so does `SafeRoll`.
+        let mut roll = RollBuilder::new().parse("2d{-4611686018427387904}").unwrap().into_roll().unwrap();
+        assert!(matches!(roll.roll(), Err(Error::OverflowNegative(_))));
+        assert!(matches!(SafeRoll::new(roll), Err(Error::OverflowNegative(_))));
+    }
+    #[test]
+    fn batches() {
+        use crate::util::{roll_batch_capped, UtilError};
+        use crate::FormatOptions;
+        let batch = roll_batch_capped("2x 3d1 + 1", 100).unwrap();
+        assert_eq!(batch.totals().collect::<Vec<_>>(), vec![4, 4]);
+        assert_eq!(batch.format(FormatOptions::new().concise()), "4, 4");
+        assert_eq!(batch.to_string(), "(3d1 → 1 + 1 + 1) + 1 = 4, (3d1 → 1 + 1 + 1) + 1 = 4");
+        let batch = roll_batch_capped("6#4d6kh3", 100).unwrap();
+        assert!(batch.totals().all(|x| (3..=18).contains(&x)));
+        assert_eq!(roll_batch_capped("1d20 + 3", 100).unwrap().results().len(), 1);
+        assert!(matches!(roll_batch_capped("26x 4d6", 100), Err(UtilError::ExceededCap)));
+        assert!(roll_batch_capped("3x", 100).is_err());
+        // Neither a prefix, nor a label.
+        assert!(matches!(roll_batch_capped("3 x d6", 100), Err(UtilError::RollError(Error::TrailingInput { .. }))));
+    }
+    #[test]
+    fn expression_lists() {
+        use crate::{roll_list, FormatOptions};
+        let results = roll_list("3d1 + 5; 2d1, d{4,4}").unwrap();
+        let totals: Vec<_> = results.results().iter().map(|x| x.total()).collect();
+        assert_eq!(totals, vec![8, 2, 4]);
+        let format = FormatOptions::new().concise().total_right();
+        assert_eq!(results.format(format), "(3d1 → 3) + 5 = 8; (2d1 → 2) = 2; 4");
+        assert_eq!(results.format(format.expression_lines()), "(3d1 → 3) + 5 = 8\n(2d1 → 2) = 2\n4");
+        assert_eq!(roll_list("1d20").unwrap().results().len(), 1);
+        assert!(roll_list("1d20;").is_err());
+        assert!(roll_list("; 1d20").is_err());
+        assert!(roll_list("1d20 ;; 2").is_err());
+    }
+    #[test]
+    fn labels() {
+        use crate::{roll_list, util::roll_batch_capped, FormatOptions};
+        let result = roll("3d1 + 5 # Longsword").unwrap();
+        assert_eq!(result.label(), Some("Longsword"));
+        assert_eq!(result.to_string(), "Longsword: (3d1 → 1 + 1 + 1) + 5");
+        assert_eq!(roll("1d8+3 # fire damage").unwrap().label(), Some("fire damage"));
+        // Labels need a `#`, so a missing operator isn't taken for one.
+        for input in &["1d8+3 fire damage", "2d6 d4", "2d6 x", "1d20 lol"] {
+            assert!(matches!(roll(input), Err(Error::TrailingInput { .. })), "{}", input);
+        }
+        let result = roll("2d1[fire] - 3[cold]").unwrap();
+        assert_eq!(result.label(), None);
+        assert_eq!(result.total(), -1);
+        assert_eq!(result.to_string(), "(2d1[fire] → 1 + 1) - 3[cold]");
+        assert!(roll("4d6kh3[fire] + d{1,2}[cold] * 2").is_ok());
+        let results = roll_list("d1 # to hit; 2d1 #damage").unwrap();
+        let labels: Vec<_> = results.results().iter().map(|x| x.label()).collect();
+        assert_eq!(labels, vec![Some("to hit"), Some("damage")]);
+        let batch = roll_batch_capped("3x 1d1 # goblins", 100).unwrap();
+        assert_eq!(batch.format(FormatOptions::new().concise()), "goblins: 1, 1, 1");
+        assert_eq!(roll("1d20 #").unwrap().label(), None);
+        assert!(roll("10dlol").is_err());
+        assert!(roll("2d6 + x").is_err());
+        assert!(roll("2d6[]").is_err());
+    }
+    #[test]
+    fn subtotals() {
+        use crate::Transform;
+        let result = roll("(2d1[fire] + 3) * 2 - 1d1[fire] * 1d1[cold] + 7[cold]").unwrap();
+        assert_eq!(result.subtotals().unwrap(), vec![(Some("fire"), 4), (None, 5), (Some("cold"), 7)]);
+        let total = result.transformed(|label| match label {
+            Some("fire") => Transform::Double,
+            Some("cold") => Transform::Halve,
+            _ => Transform::Keep,
+        });
+        assert_eq!(total.unwrap(), 8 + 5 + 3);
+        let subtotals = |x| {
+            let result = roll(x).unwrap();
+            let subtotals = result.subtotals().unwrap();
+            subtotals.into_iter().map(|(label, x)| (label.map(String::from), x)).collect::<Vec<_>>()
+        };
+        let label = |x: &str| Some(x.to_owned());
+        assert_eq!(subtotals("(2d1[fire] + 1d1[cold])"), vec![(label("fire"), 2), (label("cold"), 1)]);
+        assert_eq!(subtotals("(1d1[fire] + 10) * 2"), vec![(label("fire"), 2), (None, 20)]);
+        assert_eq!(subtotals("3 * -(2d1[fire] - 1)"), vec![(label("fire"), -6), (None, 3)]);
+        assert_eq!(subtotals("(3d1[fire] + 2) / 2 + floor(4[cold] / 2[cold])"), vec![(None, 2), (label("cold"), 2)]);
+        assert_eq!(result.transformed(|_| Transform::Keep).unwrap(), result.total());
+        assert_eq!(Transform::Halve.apply(-7).unwrap(), -4);
+        let result = roll("9223372036854775807[fire] - 1").unwrap();
+        assert!(matches!(result.transformed(|_| Transform::Double), Err(Error::OverflowPositive(_))));
+        let result = roll("9223372036854775807[fire] - 9223372036854775807 + 1[fire]").unwrap();
+        assert!(matches!(result.subtotals(), Err(Error::OverflowPositive(_))));
+    }
+    #[test]
+    fn term_views() {
+        use crate::{parse::Sign, Outcome};
+        let result = roll("4d6kh3[fire] - 2 + 3d10>=8").unwrap();
+        let terms: Vec<_> = result.terms().collect();
+        assert_eq!(terms.len(), 3);
+        assert!(matches!(terms[0].term(), Term::Dice(d) if d.count() == 4 && d.sides() == 6));
+        assert_eq!((terms[0].sign(), terms[0].label()), (Sign::Positive, Some("fire")));
+        assert_eq!(terms[0].dice().count(), 4);
+        assert_eq!(terms[0].dice().filter(|x| x.kept()).count(), 3);
+        let kept: i64 = terms[0].dice().filter(|x| x.kept()).map(|x| x.value()).sum();
+        assert_eq!(terms[0].value(), kept);
+        assert!(matches!(terms[1].term(), Term::Constant(2)));
+        assert_eq!((terms[1].sign(), terms[1].value()), (Sign::Negative, -2));
+        assert_eq!(terms[1].dice().count(), 0);
+        let successes = terms[2].dice().filter(|x| x.outcome() == Some(Outcome::Success)).count();
+        assert_eq!(terms[2].value(), successes as i64);
+        assert_eq!(terms.iter().map(|x| x.value()).sum::<i64>(), result.total());
+    }
+    #[test]
+    fn criticals() {
+        use crate::{Critical, FormatOptions};
+        assert!(roll("2d20cs>19cf<3").unwrap().to_string().starts_with("(2d20cs>19cf<3 → "));
+        assert!(roll("d20cs20cs19").is_err());
+        let result = roll("3d6cs>=1 + 2d6cf>=1 + 4d1").unwrap();
+        assert_eq!(result.criticals(), (3, 2));
+        let dice: Vec<_> = result.terms().flat_map(|x| x.dice()).map(|x| x.critical()).collect();
+        assert_eq!(&dice[..3], &[Some(Critical::Success); 3]);
+        assert_eq!(&dice[3..5], &[Some(Critical::Failure); 2]);
+        assert_eq!(&dice[5..], &[None; 4]);
+        let marked = FormatOptions::new().mark_criticals();
+        assert_eq!(roll("2d1cs1 - d1cf1").unwrap().format(marked), "(2d1cs1 → **1** + **1**) - (1d1cf1 → __1__)");
+        assert_eq!(roll("2d20kl1cs>=1").unwrap().criticals(), (1, 0));
+        // Written ranges win over the defaults, whatever the dice show.
+        for _ in 0..20 {
+            assert_eq!(roll("6d6cf>=1 + 6d6cs<=6").unwrap().criticals(), (6, 6));
+        }
+        // Concise slim rolls don't keep the dice to count crits on.
+        let mut slim = crate::builder::RollBuilder::new().parse("3d6cs>=1").unwrap().into_roll().unwrap();
+        assert_eq!(slim.slim_roll(FormatOptions::new()).unwrap().criticals(), (3, 0));
+        assert_eq!(slim.slim_roll(FormatOptions::new().concise()).unwrap().criticals(), (0, 0));
+    }
+    #[test]
+    fn replays() {
+        use crate::builder::{replay, Replay, RollBuilder};
+        let mut roll = RollBuilder::new().parse("4d6kh3 + d{1,3,5}").unwrap().into_seeded_roll(42).unwrap();
+        let seeds: Vec<_> = (0..5).map(|_| {
+            let result = roll.roll().unwrap();
+            let record = result.replay().unwrap();
+            assert_eq!(replay(record).unwrap().to_string(), result.to_string());
+            record.seed()
+        }).collect();
+        assert_eq!(seeds[0], 42);
+        let record = Replay::new(42, crate::parse::wrap_dice("4d6kh3 + d{1,3,5}").unwrap());
+        // Pinned, since replays must keep working across versions and platforms.
+        assert_eq!(replay(&record).unwrap().to_string(), "(4d6kh3 → 5 + ~~2~~ + 4 + 3) + (1d{1,3,5} → 5)");
+        assert_eq!(seeds[1], 13933846220666001134);
+    }
+    #[test]
+    fn fair_rolls() {
+        use crate::builder::{Replay, RollBuilder};
+        use crate::fair::{verify, Commitment, FairnessError};
+        let mut roll = RollBuilder::new().parse("3d6 + 2").unwrap().into_committed_roll([1; 32]).unwrap();
+        assert_eq!(roll.commitment(), Commitment::of(&[1; 32]));
+        let first = roll.roll("abc").unwrap();
+        let second = roll.roll("abc").unwrap();
+        assert_eq!((first.fairness().unwrap().nonce(), second.fairness().unwrap().nonce()), (0, 1));
+        assert_ne!(first.replay().unwrap().seed(), second.replay().unwrap().seed());
+        let server_seed = roll.reveal();
+        assert_eq!(verify(&first, &server_seed), Ok(()));
+        assert_eq!(verify(&second, &server_seed), Ok(()));
+        assert_eq!(verify(&first, &[2; 32]), Err(FairnessError::WrongSeed));
+        let expression = first.replay().unwrap().expression().clone();
+        let forged = first.clone().with_replay(Replay::new(7, expression));
+        assert_eq!(verify(&forged, &server_seed), Err(FairnessError::Mismatch));
+        assert_eq!(verify(&crate::roll("3d6").unwrap(), &server_seed), Err(FairnessError::Unrecorded));
+    }
+    #[test]
+    fn parse_errors() {
+        use crate::parse::{wrap_batch, wrap_dice, wrap_dice_list, Expected, ParseError};
+        let at = |offset, expected| Some(ParseError::InvalidExpression { offset, expected });
+        assert_eq!(wrap_dice("2d6 + * 3").err(), at(6, Expected::Number));
+        assert_eq!(wrap_dice("2d6 + + 3").err(), at(6, Expected::Number));
+        assert_eq!(wrap_dice("2d").err(), at(2, Expected::Number));
+        assert_eq!(wrap_dice("2x6").err(), at(1, Expected::Die));
+        assert_eq!(wrap_dice(" (2d6 + 1").err(), at(9, Expected::Parenthesis));
+        assert_eq!(wrap_dice("2d6 3").err(), at(4, Expected::Operator));
+        assert_eq!(wrap_dice("2d6[fé] + 1lol").err(), Some(ParseError::TrailingInput { offset: 12, column: 12 }));
+        assert_eq!(wrap_dice("1 + 12d0kh1").err(), Some(ParseError::InvalidDie { offset: 4 }));
+        assert_eq!(wrap_dice("(d0)").err(), Some(ParseError::InvalidDie { offset: 1 }));
+        assert_eq!(wrap_dice("2d99999999999999999999").err(), Some(ParseError::TooLarge { offset: 2 }));
+        assert_eq!(wrap_dice("1d6kh99999999999999999999").err(), Some(ParseError::TooLarge { offset: 5 }));
+        assert_eq!(wrap_dice("d{1,99999999999999999999}").err(), Some(ParseError::TooLarge { offset: 4 }));
+        assert!(matches!(roll("d20 +5lol"), Err(Error::TrailingInput { offset: 6, column: 7 })));
+        assert!(matches!(roll("2d0"), Err(Error::InvalidDie)));
+        assert!(matches!(roll("99999999999999999999"), Err(Error::TooLarge)));
+        assert!(matches!(roll("2d6 +"), Err(Error::InvalidExpression(_))));
+        assert_eq!(wrap_dice("").err(), at(0, Expected::Number));
+        assert_eq!(wrap_dice_list("d20; 2d6 + x").err(), at(11, Expected::Number));
+        assert_eq!(wrap_batch("12x 2d6 *").err(), at(9, Expected::Number));
+        let error = wrap_dice("(2d6 + 1 4").unwrap_err();
+        assert_eq!(error.render("(2d6 + 1 4"), "(2d6 + 1 4\n         ^ expected `)`");
+        assert_eq!(error.to_string(), "you've specified an invalid dice expression, expected `)` at byte 9");
+    }
+    #[test]
+    fn simplify() {
+        use crate::parse::Expression;
+        let simplify = |x: &str| x.parse::<Expression>().unwrap().simplify().to_string();
+        assert_eq!(simplify("1d6 + 1d6 + 2 + 3 - 1"), "2d6 + 4");
+        assert_eq!(simplify("2d20kh1 + 2d20kh1 + 1d10>=8 + 1d10>=8"), "2d20kh1 + 2d20kh1 + 2d10>=8");
+        assert_eq!(simplify("1d4 - 1d4 - 1d4 + 1d4[fire] + 2[fire] + 1 + 3[fire]"), "1d4 - 2d4 + 1d4[fire] + 5[fire] + 1");
+        assert_eq!(simplify("2 * (1 + 1d6 + 2 + 1d6) + 1dF + 1dF + 1d% + 1d100"), "2 * (2d6 + 3) + 2dF + 1d% + 1d100");
+        assert_eq!(simplify("3 - 3 # nothing"), "0 # nothing");
+        assert_eq!(simplify("3 - 2 * 4 + 1d6"), "3 - 2 * 4 + 1d6");
+        assert_eq!(simplify("(2 - 2) * 1d6"), "(0) * 1d6");
+        assert_eq!(simplify("9223372036854775807 + 1d4 + 5"), "1d4 + 9223372036854775807 + 5");
+        let expression: Expression = "1d6r1 + 3 + 1d6r1 - 1d4 + 1d4 * 2 - 1".parse().unwrap();
+        let (before, after) = (expression.distribution().unwrap(), expression.simplify().distribution().unwrap());
+        assert_eq!((before.min(), before.max()), (after.min(), after.max()));
+        assert!(before.iter().zip(after.iter()).all(|((a, p), (b, q))| a == b && (p - q).abs() < 1e-12));
+    }
+
+    #[test]
+    fn expression_builder() {
+        use crate::parse::{Expression, Sign};
+        let expression = Expression::builder()
+            .dice(1, 20)
+            .unwrap()
+            .constant(5)
+            .constant(-2)
+            .sign(Sign::Negative)
+            .dice(2, 4)
+            .unwrap()
+            .sign(Sign::Negative)
+            .constant(-1)
+            .build();
+        assert_eq!(expression.to_string(), "1d20 + 5 - 2 - 2d4 + 1");
+        assert_eq!(expression.to_string().parse::<Expression>().unwrap(), expression);
+        assert_eq!(Expression::builder().build().to_string(), "0");
+        let min = Expression::builder().constant(i64::MIN).build();
+        assert_eq!(min.to_string(), "-9223372036854775807 - 1");
+        assert!(Expression::builder().dice(1, 0).is_err());
+        assert!(Expression::builder().dice(-1, 6).is_err());
+        let result = crate::builder::RollBuilder::new()
+            .with_expression(Expression::builder().dice(3, 1).unwrap().constant(2).build())
+            .into_roll()
+            .unwrap()
+            .roll()
+            .unwrap();
+        assert_eq!(result.total(), 5);
+    }
+
+    mod round_trip {
+        use crate::parse::Expression;
+        use proptest::prelude::*;
+
+        fn comparison() -> impl Strategy<Value = String> {
+            let op = prop::sample::select(vec!["", "=", "<", ">", "<=", ">="]);
+            (op, 0..30i64).prop_map(|(op, n)| format!("{}{}", op, n))
+        }
+        /// Some of each kind of modifier, in any order.
+        fn modifiers() -> impl Strategy<Value = String> {
+            let keep = (prop::sample::select(vec!["kh", "kl", "dh", "dl"]), prop::option::of(0..10i64))
+                .prop_map(|(k, n)| format!("{}{}", k, n.map(|x| x.to_string()).unwrap_or_default()));
+            let explode = (prop::sample::select(vec!["!", "!!", "!p"]), prop::option::of(comparison()))
+                .prop_map(|(e, c)| format!("{}{}", e, c.unwrap_or_default()));
+            let reroll = (prop::sample::select(vec!["r", "ro"]), prop::option::of(comparison()))
+                .prop_map(|(r, c)| format!("{}{}", r, c.unwrap_or_default()));
+            let target = (prop::sample::select(vec!["=", "<", ">", "<=", ">="]), 0..30i64, prop::option::of(comparison()))
+                .prop_map(|(op, n, f)| format!("{}{}{}", op, n, f.map(|x| format!("f{}", x)).unwrap_or_default()));
+            let critical = (prop::sample::select(vec!["cs", "cf"]), comparison()).prop_map(|(c, x)| format!("{}{}", c, x));
+            let each = |x: BoxedStrategy<String>| prop::option::of(x).prop_map(Option::unwrap_or_default);
+            (each(keep.boxed()), each(explode.boxed()), each(reroll.boxed()), each(target.boxed()), each(critical.boxed()))
+                .prop_map(|(a, b, c, d, e)| vec![a, b, c, d, e])
+                .prop_shuffle()
+                .prop_map(|x| x.concat())
+        }
+        fn term() -> impl Strategy<Value = String> {
+            let count = prop::option::of(0..20i64).prop_map(|x| x.map(|x| x.to_string()).unwrap_or_default());
+            let faces = prop::collection::vec(-5..10i64, 1..5)
+                .prop_map(|x| format!("{{{}}}", x.iter().map(|x| x.to_string()).collect::<Vec<_>>().join(", ")));
+            let sides = prop_oneof![(1..100i64).prop_map(|x| x.to_string()), Just("%".to_owned()), faces];
+            let dice = (count.clone(), sides, modifiers()).prop_map(|(n, s, m)| format!("{}d{}{}", n, s, m));
+            let fate = count.prop_map(|n| format!("{}dF", n));
+            let label = prop::option::of("[a-z]{1,5}").prop_map(|x| x.map(|x| format!("[{}]", x)).unwrap_or_default());
+            (prop_oneof![(0..1000i64).prop_map(|x| x.to_string()), dice, fate], label).prop_map(|(t, l)| t + &l)
+        }
+        fn space() -> impl Strategy<Value = &'static str> {
+            prop::sample::select(vec!["", " ", "  ", "\t"])
+        }
+        fn sign() -> impl Strategy<Value = &'static str> {
+            prop::sample::select(vec!["", "-", "+"])
+        }
+        /// Signs are only allowed at the start, after `(`, and after `*` or `/`.
+        fn expression() -> impl Strategy<Value = String> {
+            let unsigned = term().prop_recursive(4, 24, 4, |inner| {
+                let op = prop::sample::select(vec!["+", "-", "*", "/"]);
+                let product = prop::sample::select(vec!["*", "/"]);
+                let group = prop::sample::select(vec!["", "floor"]);
+                prop_oneof![
+                    (inner.clone(), space(), op, space(), inner.clone())
+                        .prop_map(|(a, s, op, t, b)| format!("{}{}{}{}{}", a, s, op, t, b)),
+                    (inner.clone(), space(), product, space(), sign(), inner.clone())
+                        .prop_map(|(a, s, op, t, sign, b)| format!("{}{}{}{}{}{}", a, s, op, t, sign, b)),
+                    (group, space(), sign(), inner, space())
+                        .prop_map(|(g, s, sign, x, t)| format!("{}({}{}{}{})", g, s, sign, x, t)),
+                ]
+            });
+            (sign(), unsigned).prop_map(|(s, x)| s.to_owned() + &x)
+        }
+
+        #[test]
+        fn explode_before_target() {
+            let expression: Expression = "(d1=0!+0)".parse().unwrap();
+            assert_eq!(expression.to_string(), "(1d1=0! + 0)");
+            let expression: Expression = "(d{-1}=0!)".parse().unwrap();
+            assert_eq!(expression.to_string(), "(1d{-1}=0!)");
+            assert_eq!(expression.to_string().parse::<Expression>().unwrap(), expression);
+            // A bare explode written before a target still comes back the same.
+            let expression: Expression = "d6!r1>=5".parse().unwrap();
+            assert_eq!(expression.to_string(), "1d6r1>=5!");
+            assert_eq!(expression.to_string().parse::<Expression>().unwrap(), expression);
+        }
+
+        proptest! {
+            #[test]
+            fn canonical_forms_parse_back(
+                input in expression(),
+                label in prop::option::of("[A-Za-z][a-z ]{0,8}[a-z]"),
+            ) {
+                let input = match label {
+                    Some(label) => format!("{} # {}", input, label),
+                    None => input,
+                };
+                // Repeated modifiers, like the second `r` in `d6rr`, make some inputs invalid.
+                let parsed = input.parse::<Expression>();
+                prop_assume!(parsed.is_ok());
+                let parsed = parsed.unwrap();
+                let canonical = parsed.to_string();
+                let reparsed: Expression = canonical.parse().unwrap();
+                prop_assert_eq!(&reparsed, &parsed);
+                prop_assert_eq!(reparsed.to_string(), canonical);
+                for simple in &[parsed.simplify(), parsed.simplify_in_order()] {
+                    prop_assert_eq!(&simple.to_string().parse::<Expression>().unwrap(), simple);
+                }
+            }
+        }
+    }
 }
@@ -2,27 +2,362 @@
 use crate::post::FormatOptions;
 use nom::{
     branch::alt,
-    bytes::complete::{tag, take_while1},
-    combinator::opt,
-    error::ErrorKind::TooLarge,
+    bytes::complete::{tag, take_till, take_while1},
+    combinator::{map, opt, peek},
+    error::ErrorKind::{self, TooLarge},
     multi::many0,
     sequence::tuple,
     Err::Failure,
-    IResult,
+    IResult, Offset,
 };
+use std::collections::HashMap;
 use std::fmt::Display;
 use std::fmt::Formatter;
+use std::str::FromStr;
+use std::sync::Arc;
 use std::ops::{Mul, Neg};
 use thiserror::Error;
-// use std::collections::HashMap;
 
-#[derive(Debug, Copy, Clone, Error)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Error)]
 pub enum ParseError {
-    #[error("you've specified an invalid dice expression")]
-    InvalidExpression,
+    /// The input went wrong `offset` bytes in, where something else was `expected`.
+    #[error("you've specified an invalid dice expression, expected {expected} at byte {offset}")]
+    InvalidExpression { offset: usize, expected: Expected },
+    /// A die with no sides, like `d0`, starting `offset` bytes in.
+    #[error("dice need at least one side, at byte {offset}")]
+    InvalidDie { offset: usize },
+    /// A number too large for an `i64`, starting `offset` bytes in.
+    #[error("number at byte {offset} is too large")]
+    TooLarge { offset: usize },
+    /// Input left over after a whole expression, from `offset` bytes in,
+    /// which is the `column`th character, counting from one.
+    #[error("couldn't understand the input from column {column}")]
+    TrailingInput { offset: usize, column: usize },
+    /// Groups or operators nested deeper than `NESTING_LIMIT`,
+    /// going too deep `offset` bytes in.
+    #[error("expression is nested too deeply at byte {offset}")]
+    TooDeep { offset: usize },
+}
+impl ParseError {
+    /// Bytes into the input where this went wrong.
+    pub fn offset(&self) -> usize {
+        match *self {
+            ParseError::InvalidExpression { offset, .. }
+            | ParseError::InvalidDie { offset }
+            | ParseError::TooLarge { offset }
+            | ParseError::TrailingInput { offset, .. }
+            | ParseError::TooDeep { offset } => offset,
+        }
+    }
+    /// Show the input this error came from, with a caret under where it went wrong.
+    /// ```
+    /// # use mice::{builder::RollBuilder, ParseError};
+    /// let input = "2d6 + * 3";
+    /// let error = RollBuilder::new().parse(input).err().unwrap();
+    /// assert_eq!(error.render(input), "2d6 + * 3\n      ^ expected a number");
+    /// ```
+    pub fn render(&self, input: &str) -> String {
+        let note = match self {
+            ParseError::InvalidExpression { expected, .. } => format!("expected {}", expected),
+            ParseError::InvalidDie { .. } => "dice need at least one side".into(),
+            ParseError::TooLarge { .. } => "number is too large".into(),
+            ParseError::TrailingInput { .. } => "couldn't understand this".into(),
+            ParseError::TooDeep { .. } => "nested too deeply".into(),
+        };
+        let offset = self.offset();
+        let column = input.get(..offset).map(|x| x.chars().count()).unwrap_or(offset);
+        format!("{}\n{}^ {}", input, " ".repeat(column), note)
+    }
 }
 
-#[derive(Debug, Copy, Clone)]
+/// What was expected where a dice expression went wrong.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Expected {
+    /// A number, or a term, which starts with one.
+    Number,
+    /// The `d` of a dice term, as after the `2` in `2x6`.
+    Die,
+    /// A binary operator, like `+` or `*`.
+    Operator,
+    /// The `)` closing a group.
+    Parenthesis,
+}
+impl Display for Expected {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Expected::Number => write!(f, "a number"),
+            Expected::Die => write!(f, "`d`"),
+            Expected::Operator => write!(f, "an operator"),
+            Expected::Parenthesis => write!(f, "`)`"),
+        }
+    }
+}
+
+/// Selection of which dice in a term count towards its total.
+///
+/// Counts larger than the number of dice rolled simply
+/// keep or drop all of them.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Keep {
+    /// `khN`, keep the `N` highest dice.
+    Highest(i64),
+    /// `klN`, keep the `N` lowest dice.
+    Lowest(i64),
+    /// `dhN`, drop the `N` highest dice.
+    DropHighest(i64),
+    /// `dlN`, drop the `N` lowest dice.
+    DropLowest(i64),
+}
+impl Keep {
+    /// Number of the lowest dice to drop, and number of the highest
+    /// dice to drop, from a term with `number` dice.
+    pub(crate) fn dropped(self, number: i64) -> (i64, i64) {
+        match self {
+            Keep::Highest(n) => ((number - n).max(0), 0),
+            Keep::Lowest(n) => (0, (number - n).max(0)),
+            Keep::DropHighest(n) => (0, n.min(number)),
+            Keep::DropLowest(n) => (n.min(number), 0),
+        }
+    }
+}
+impl Display for Keep {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Keep::Highest(n) => write!(f, "kh{}", n),
+            Keep::Lowest(n) => write!(f, "kl{}", n),
+            Keep::DropHighest(n) => write!(f, "dh{}", n),
+            Keep::DropLowest(n) => write!(f, "dl{}", n),
+        }
+    }
+}
+
+/// A test against the face of a single die,
+/// as used in thresholds like the `>5` in `d6!>5`.
+///
+/// A bare number, as in `d6!5`, is a test for equality.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Comparison {
+    Equal(i64),
+    Less(i64),
+    Greater(i64),
+    LessOrEqual(i64),
+    GreaterOrEqual(i64),
+}
+impl Comparison {
+    /// Whether a die face passes this test.
+    pub fn matches(self, face: i64) -> bool {
+        match self {
+            Comparison::Equal(x) => face == x,
+            Comparison::Less(x) => face < x,
+            Comparison::Greater(x) => face > x,
+            Comparison::LessOrEqual(x) => face <= x,
+            Comparison::GreaterOrEqual(x) => face >= x,
+        }
+    }
+}
+impl Display for Comparison {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Comparison::Equal(x) => write!(f, "={}", x),
+            Comparison::Less(x) => write!(f, "<{}", x),
+            Comparison::Greater(x) => write!(f, ">{}", x),
+            Comparison::LessOrEqual(x) => write!(f, "<={}", x),
+            Comparison::GreaterOrEqual(x) => write!(f, ">={}", x),
+        }
+    }
+}
+
+/// Maximum number of times a single die may explode.
+///
+/// Without this, a die like `d1!` would never stop rolling.
+pub const EXPLOSION_LIMIT: i64 = 20;
+
+/// Maximum depth of groups and chained `*` or `/` in an expression.
+///
+/// Parsing, rolling and displaying expressions all recurse through them,
+/// so without this, a long enough input would overflow the stack.
+pub const NESTING_LIMIT: usize = 64;
+
+/// How the extra rolls of an exploding die are combined.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ExplodeKind {
+    /// `!`, every extra roll is shown as a die of its own.
+    Standard,
+    /// `!!`, extra rolls are folded into the value of the die that exploded.
+    Compounding,
+    /// `!p`, like `Standard`, but each extra roll is reduced by one.
+    Penetrating,
+}
+
+/// Rolling another die whenever a die shows a face matching `threshold`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Explode {
+    pub(crate) kind: ExplodeKind,
+    pub(crate) threshold: Comparison,
+}
+impl Explode {
+    pub fn kind(&self) -> ExplodeKind {
+        self.kind
+    }
+    /// Faces that cause another die to be rolled.
+    /// Defaults to the highest face of the die.
+    pub fn threshold(&self) -> Comparison {
+        self.threshold
+    }
+}
+impl Display for ExplodeKind {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            ExplodeKind::Standard => write!(f, "!"),
+            ExplodeKind::Compounding => write!(f, "!!"),
+            ExplodeKind::Penetrating => write!(f, "!p"),
+        }
+    }
+}
+
+/// Maximum number of times a single die may be rerolled by `r`.
+pub const REROLL_LIMIT: i64 = 20;
+
+/// Replacing a die whose face matches `condition` with a new roll.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Reroll {
+    pub(crate) once: bool,
+    pub(crate) condition: Comparison,
+}
+impl Reroll {
+    /// `ro`, reroll at most once, instead of until the condition fails.
+    pub fn once(&self) -> bool {
+        self.once
+    }
+    /// Faces that get rerolled. Defaults to `1`.
+    pub fn condition(&self) -> Comparison {
+        self.condition
+    }
+    /// Greatest number of times a single die may be rerolled.
+    pub(crate) fn limit(&self) -> i64 {
+        if self.once {
+            1
+        } else {
+            REROLL_LIMIT
+        }
+    }
+}
+
+/// Counting dice that pass a test, instead of summing them.
+///
+/// `10d10>=8f1` counts each die showing 8 or more as a success,
+/// and subtracts one for each die showing a 1.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Target {
+    pub(crate) success: Comparison,
+    pub(crate) failure: Option<Comparison>,
+}
+impl Target {
+    /// Faces counted as successes.
+    pub fn success(&self) -> Comparison {
+        self.success
+    }
+    /// Faces counted as failures, if any.
+    pub fn failure(&self) -> Option<Comparison> {
+        self.failure
+    }
+}
+impl Display for Target {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.success)?;
+        if let Some(failure) = self.failure {
+            write!(f, "f")?;
+            write_face_test(f, failure)?;
+        }
+        Ok(())
+    }
+}
+
+/// Write a modifier's face test, leaving off the `=` of equality
+/// tests so that `r1` doesn't come out as `r=1`.
+fn write_face_test(f: &mut Formatter, test: Comparison) -> std::fmt::Result {
+    match test {
+        Comparison::Equal(x) => write!(f, "{}", x),
+        x => write!(f, "{}", x),
+    }
+}
+
+/// A table of faces for dice that aren't numbered `1` through `N`,
+/// either written inline as `d{1,1,2,3,5,8}`, or defined with a name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Faces {
+    name: Option<Arc<str>>,
+    values: Arc<[i64]>,
+}
+impl Faces {
+    /// The name this die was defined with, if it wasn't written inline.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+    /// Every face of the die, each equally likely to be rolled.
+    pub fn values(&self) -> &[i64] {
+        &self.values
+    }
+}
+impl Display for Faces {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "{}", name),
+            None => {
+                let faces: Vec<_> = self.values.iter().map(|x| x.to_string()).collect();
+                write!(f, "{{{}}}", faces.join(","))
+            }
+        }
+    }
+}
+
+/// Dice defined by name, for use in expressions like `2dBoost`.
+///
+/// Names are made of ASCII letters, and `F` is reserved for Fate dice.
+/// Where one name is a prefix of another, the longest one that matches is used,
+/// so that modifiers may directly follow a name, as in `4dBoostkh2`.
+#[derive(Debug, Clone, Default)]
+pub struct NamedDice {
+    dice: HashMap<String, Faces>,
+}
+impl NamedDice {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Define a die with the given faces, replacing any previous definition of `name`.
+    ///
+    /// Fails if `faces` is empty, or if `name` isn't a valid name.
+    pub fn define(&mut self, name: &str, faces: &[i64]) -> Result<(), InvalidDie> {
+        if name.is_empty() || name == "F" || !name.chars().all(|c| c.is_ascii_alphabetic()) {
+            return Err(InvalidDie);
+        }
+        let faces = Faces {
+            name: Some(name.into()),
+            values: checked_faces(faces.to_vec())?,
+        };
+        self.dice.insert(name.to_string(), faces);
+        Ok(())
+    }
+    /// The longest defined name at the start of `input`.
+    fn longest_prefix(&self, input: &str) -> Option<(&str, &Faces)> {
+        self.dice
+            .iter()
+            .filter(|(name, _)| input.starts_with(name.as_str()))
+            .max_by_key(|(name, _)| name.len())
+            .map(|(name, faces)| (name.as_str(), faces))
+    }
+}
+
+/// There must be at least one face.
+fn checked_faces(faces: Vec<i64>) -> Result<Arc<[i64]>, InvalidDie> {
+    if faces.is_empty() {
+        Err(InvalidDie)
+    } else {
+        Ok(faces.into())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DiceTerm {
     /// Negative numbers of dice are
     /// incorrect, but matching integer
@@ -36,6 +371,16 @@ pub struct DiceTerm {
     // due to our storing these things as
     // signed integer types,
     // despite their always being positive.
+    pub(crate) keep: Option<Keep>,
+    pub(crate) explode: Option<Explode>,
+    pub(crate) reroll: Option<Reroll>,
+    pub(crate) target: Option<Target>,
+    /// Crit ranges written on this term, as the `cs>19` and `cf1` in `d20cs>19cf1`.
+    /// Left unset when they should default to the highest and lowest faces.
+    pub(crate) critical_success: Option<Comparison>,
+    pub(crate) critical_failure: Option<Comparison>,
+    /// When present, `size` is the number of faces.
+    pub(crate) faces: Option<Faces>,
 }
 impl DiceTerm {
     /// Creation of a `Die` may fail if:
@@ -47,15 +392,179 @@ impl DiceTerm {
         if size < 1 || number < 0 {
             Err(InvalidDie)
         } else {
-            Ok(DiceTerm { number, size })
+            Ok(DiceTerm {
+                number,
+                size,
+                keep: None,
+                explode: None,
+                reroll: None,
+                target: None,
+                critical_success: None,
+                critical_failure: None,
+                faces: None,
+            })
         }
     }
+    pub(crate) fn with_faces(number: i64, faces: Faces) -> Result<Self, InvalidDie> {
+        let mut die = DiceTerm::new(number, faces.values.len() as i64)?;
+        die.faces = Some(faces);
+        Ok(die)
+    }
     pub fn count(&self) -> u64 {
         self.number as _
     }
     pub fn sides(&self) -> u64 {
         self.size as _
     }
+    /// The faces of this die, if they aren't `1` through `sides()`.
+    pub fn faces(&self) -> Option<&Faces> {
+        self.faces.as_ref()
+    }
+    /// The highest face of this die.
+    pub(crate) fn max_face(&self) -> i64 {
+        match &self.faces {
+            Some(faces) => faces.values.iter().copied().max().unwrap_or(0),
+            None => self.size,
+        }
+    }
+    /// The lowest face of this die.
+    pub(crate) fn min_face(&self) -> i64 {
+        match &self.faces {
+            Some(faces) => faces.values.iter().copied().min().unwrap_or(0),
+            None => 1,
+        }
+    }
+    /// The keep or drop modifier on this term, if any.
+    pub fn keep(&self) -> Option<Keep> {
+        self.keep
+    }
+    /// The explode modifier on this term, if any.
+    pub fn explode(&self) -> Option<Explode> {
+        self.explode
+    }
+    /// The reroll modifier on this term, if any.
+    pub fn reroll(&self) -> Option<Reroll> {
+        self.reroll
+    }
+    /// The success counting target of this term, if any.
+    /// A term with a target evaluates to its number of successes.
+    pub fn target(&self) -> Option<Target> {
+        self.target
+    }
+    /// The faces on which a die of this term is a critical success.
+    /// Unless written otherwise, that's the highest face,
+    /// for dice with more than one face.
+    ///
+    /// Faces in both ranges are successes, unless only
+    /// the failure range was written out, as in `d6cf>=1`.
+    pub fn critical_success(&self) -> Option<Comparison> {
+        self.critical_success.or_else(|| self.default_critical(self.max_face()))
+    }
+    /// The faces on which a die of this term is a critical failure.
+    /// Unless written otherwise, that's the lowest face,
+    /// for dice with more than one face.
+    pub fn critical_failure(&self) -> Option<Comparison> {
+        self.critical_failure.or_else(|| self.default_critical(self.min_face()))
+    }
+    fn default_critical(&self, face: i64) -> Option<Comparison> {
+        if self.max_face() > self.min_face() {
+            Some(Comparison::Equal(face))
+        } else {
+            None
+        }
+    }
+    /// Greatest number of dice this term may roll.
+    pub(crate) fn max_rolls(&self) -> i64 {
+        let explosions = self.explode.map(|_| EXPLOSION_LIMIT).unwrap_or(0);
+        let rerolls = self.reroll.map(|r| r.limit()).unwrap_or(0);
+        self.number.saturating_mul(1 + explosions + rerolls)
+    }
+    /// Record a modifier on this term, refusing to
+    /// overwrite one of the same kind.
+    fn modify(&mut self, modifier: Modifier) -> bool {
+        match modifier {
+            Modifier::Keep(k) if self.keep.is_none() => self.keep = Some(k),
+            Modifier::Explode(kind, threshold) if self.explode.is_none() => {
+                self.explode = Some(Explode {
+                    kind,
+                    threshold: threshold.unwrap_or(Comparison::Equal(self.max_face())),
+                })
+            }
+            Modifier::Reroll(r) if self.reroll.is_none() => self.reroll = Some(r),
+            Modifier::Target(t) if self.target.is_none() => self.target = Some(t),
+            Modifier::CriticalSuccess(c) if self.critical_success.is_none() => {
+                self.critical_success = Some(c)
+            }
+            Modifier::CriticalFailure(c) if self.critical_failure.is_none() => {
+                self.critical_failure = Some(c)
+            }
+            _ => return false,
+        }
+        true
+    }
+}
+impl DiceTerm {
+    /// Write this term with something other than its size after the `d`,
+    /// which is needed for percentile dice.
+    fn write_with_sides(&self, f: &mut Formatter, sides: &dyn Display) -> std::fmt::Result {
+        write!(f, "{}d{}", self.number, sides)?;
+        if let Some(r) = self.reroll {
+            write!(f, "{}", if r.once { "ro" } else { "r" })?;
+            write_face_test(f, r.condition)?;
+        }
+        let bare = matches!(self.explode, Some(e) if e.threshold == Comparison::Equal(self.max_face()));
+        // A target directly after a bare explode would be taken for its threshold,
+        // so it goes first. The threshold can't be written instead, as it may be negative.
+        let target_first = bare && self.keep.is_none();
+        if let (true, Some(t)) = (target_first, self.target) {
+            write!(f, "{}", t)?;
+        }
+        if let Some(e) = self.explode {
+            write!(f, "{}", e.kind)?;
+            if !bare {
+                write_face_test(f, e.threshold)?;
+            }
+        }
+        if let Some(k) = self.keep {
+            write!(f, "{}", k)?;
+        }
+        if let (false, Some(t)) = (target_first, self.target) {
+            write!(f, "{}", t)?;
+        }
+        if let Some(c) = self.critical_success {
+            write!(f, "cs")?;
+            write_face_test(f, c)?;
+        }
+        if let Some(c) = self.critical_failure {
+            write!(f, "cf")?;
+            write_face_test(f, c)?;
+        }
+        Ok(())
+    }
+}
+impl Display for DiceTerm {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match &self.faces {
+            Some(faces) => self.write_with_sides(f, faces),
+            None => self.write_with_sides(f, &self.size),
+        }
+    }
+}
+
+/// `NdF`, Fate dice, each of which shows `-1`, `0` or `+1`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FateTerm {
+    pub(crate) number: i64,
+}
+impl FateTerm {
+    pub fn count(&self) -> u64 {
+        self.number as _
+    }
+}
+impl Display for FateTerm {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "{}dF", self.number)
+    }
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -70,21 +579,26 @@ impl Display for ConstantTerm {
     }
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Term {
     Dice(DiceTerm),
     Constant(i64),
+    Fate(FateTerm),
+    /// `Nd%`, which is the same as `Nd100`, save for how it's written.
+    Percentile(DiceTerm),
 }
 impl Display for Term {
     fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
         match self {
-            Term::Dice(x) => write!(f, "{}d{}", x.number, x.size),
+            Term::Dice(x) => write!(f, "{}", x),
             Term::Constant(x) => write!(f, "{}", x),
+            Term::Fate(x) => write!(f, "{}", x),
+            Term::Percentile(x) => x.write_with_sides(f, &'%'),
         }
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Sign {
     Positive,
     Negative,
@@ -120,10 +634,12 @@ impl Display for Sign {
     }
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) struct Expr {
     pub(crate) term: Term,
     pub(crate) sign: Sign,
+    /// What this term is for, as the `fire` in `2d6[fire]`.
+    pub(crate) label: Option<Arc<str>>,
 }
 impl Expr {
     pub(crate) fn format(&self, options: FormatOptions) -> String {
@@ -136,10 +652,13 @@ impl Expr {
         if !ignore_sign {
             match self.sign {
                 Sign::Positive => (),
-                Sign::Negative => nstr.push_str("-"),
+                Sign::Negative => nstr.push('-'),
             }
         }
         nstr.push_str(&format!("{}", self.term));
+        if let Some(label) = &self.label {
+            nstr.push_str(&format!("[{}]", label));
+        }
         nstr
     }
 }
@@ -149,36 +668,310 @@ impl Display for Expr {
     }
 }
 
-#[derive(Debug)]
+/// Operators that bind more tightly than `+` and `-`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum Operator {
+    Multiply,
+    /// Division rounds down, like Python's `//`.
+    Divide,
+}
+impl Display for Operator {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Operator::Multiply => write!(f, "*"),
+            Operator::Divide => write!(f, "/"),
+        }
+    }
+}
+
+/// A dice expression tree.
+///
+/// Leaves are terms, which are `Expr`s in a parsed expression,
+/// and are paired with their results in an evaluated one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Node<T = Expr> {
+    Term(T),
+    /// Negation of anything other than a term, which carry their own signs.
+    Neg(Box<Node<T>>),
+    /// Operands added together.
+    /// Subtraction is represented by negating the operand subtracted.
+    Sum(Vec<Node<T>>),
+    Product(Box<Node<T>>, Operator, Box<Node<T>>),
+    /// A parenthesized sub-expression.
+    Group(Box<Node<T>>),
+    /// `floor(...)`. Since division already rounds down,
+    /// this is the same as a `Group`, but is kept for display.
+    Floor(Box<Node<T>>),
+}
+impl<T> Node<T> {
+    fn collect_leaves<'a>(&'a self, leaves: &mut Vec<&'a T>) {
+        match self {
+            Node::Term(x) => leaves.push(x),
+            Node::Neg(x) | Node::Group(x) | Node::Floor(x) => x.collect_leaves(leaves),
+            Node::Sum(xs) => xs.iter().for_each(|x| x.collect_leaves(leaves)),
+            Node::Product(lhs, _, rhs) => {
+                lhs.collect_leaves(leaves);
+                rhs.collect_leaves(leaves);
+            }
+        }
+    }
+    /// Terms of this tree, from left to right.
+    pub(crate) fn leaves(&self) -> Vec<&T> {
+        let mut leaves = Vec::new();
+        self.collect_leaves(&mut leaves);
+        leaves
+    }
+    /// Build a tree of the same shape, by applying `f` to every term from left to right.
+    pub(crate) fn try_map<U, E, F>(&self, f: &mut F) -> Result<Node<U>, E>
+    where
+        F: FnMut(&T) -> Result<U, E>,
+    {
+        Ok(match self {
+            Node::Term(x) => Node::Term(f(x)?),
+            Node::Neg(x) => Node::Neg(Box::new(x.try_map(f)?)),
+            Node::Group(x) => Node::Group(Box::new(x.try_map(f)?)),
+            Node::Floor(x) => Node::Floor(Box::new(x.try_map(f)?)),
+            Node::Sum(xs) => Node::Sum(xs.iter().map(|x| x.try_map(f)).collect::<Result<_, _>>()?),
+            Node::Product(lhs, op, rhs) => {
+                Node::Product(Box::new(lhs.try_map(f)?), *op, Box::new(rhs.try_map(f)?))
+            }
+        })
+    }
+    /// The operands of this node, if it were added to something.
+    pub(crate) fn into_operands(self) -> Vec<Node<T>> {
+        match self {
+            Node::Sum(xs) => xs,
+            x => vec![x],
+        }
+    }
+}
+impl Node {
+    /// Apply a sign to this node, folding it into the node's term if it is one.
+    fn signed(self, sign: Sign) -> Self {
+        match (self, sign) {
+            (x, Sign::Positive) => x,
+            (Node::Term(x), Sign::Negative) => Node::Term(Expr { sign: -x.sign, ..x }),
+            (Node::Neg(x), Sign::Negative) => *x,
+            (x, Sign::Negative) => Node::Neg(Box::new(x)),
+        }
+    }
+    /// Write this tree as it's parsed, with a space either side of each binary operator.
+    fn write_canonical(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Node::Term(x) => write!(f, "{}", x),
+            Node::Neg(x) => {
+                write!(f, "-")?;
+                x.write_canonical(f)
+            }
+            Node::Sum(xs) => {
+                for (i, x) in xs.iter().enumerate() {
+                    match x {
+                        _ if i == 0 => x.write_canonical(f)?,
+                        Node::Term(t) if t.sign == Sign::Negative => {
+                            write!(f, " - {}", t.format(FormatOptions::new().exclude_sign()))?
+                        }
+                        Node::Neg(x) => {
+                            write!(f, " - ")?;
+                            x.write_canonical(f)?
+                        }
+                        x => {
+                            write!(f, " + ")?;
+                            x.write_canonical(f)?
+                        }
+                    }
+                }
+                Ok(())
+            }
+            Node::Product(lhs, op, rhs) => {
+                lhs.write_canonical(f)?;
+                write!(f, " {} ", op)?;
+                rhs.write_canonical(f)
+            }
+            Node::Group(x) => {
+                write!(f, "(")?;
+                x.write_canonical(f)?;
+                write!(f, ")")
+            }
+            Node::Floor(x) => {
+                write!(f, "floor(")?;
+                x.write_canonical(f)?;
+                write!(f, ")")
+            }
+        }
+    }
+    /// Simplify every sum in this tree. See `Expression::simplify`.
+    fn simplified(&self, preserve_order: bool) -> Node {
+        let simplified = |x: &Node| Box::new(x.simplified(preserve_order));
+        match self {
+            Node::Term(x) => Node::Term(x.clone()),
+            Node::Neg(x) => Node::Neg(simplified(x)),
+            Node::Group(x) => Node::Group(simplified(x)),
+            Node::Floor(x) => Node::Floor(simplified(x)),
+            Node::Product(lhs, op, rhs) => Node::Product(simplified(lhs), *op, simplified(rhs)),
+            Node::Sum(xs) => {
+                let xs = xs.iter().map(|x| x.simplified(preserve_order)).collect();
+                let mut xs = simplify_sum(xs, preserve_order);
+                // As parsed, a sum of one operand is just that operand.
+                if xs.len() == 1 {
+                    xs.pop().unwrap()
+                } else {
+                    Node::Sum(xs)
+                }
+            }
+        }
+    }
+    /// Whether this is a sum of terms, with nothing fancier.
+    /// These are the only expressions that existed before `*`, `/` and parentheses.
+    pub(crate) fn is_flat(&self) -> bool {
+        match self {
+            Node::Sum(xs) => xs.iter().all(|x| matches!(x, Node::Term(_))),
+            Node::Term(_) => true,
+            _ => false,
+        }
+    }
+}
+
+/// A parsed dice expression.
+///
+/// Expressions display in a canonical form, which parses back to the same expression.
+/// Binary operators have a space either side, dice always have a count, as in `1d20`,
+/// modifiers are in a fixed order, and labels are written as comments.
+/// ```
+/// # use mice::parse::Expression;
+/// let expression: Expression = "d20kh1+ 2*(d4-1)   #fire".parse()?;
+/// assert_eq!(expression.to_string(), "1d20kh1 + 2 * (1d4 - 1) # fire");
+/// assert_eq!(expression.to_string().parse::<Expression>()?, expression);
+/// # Ok::<(), mice::ParseError>(())
+/// ```
+///
+/// Named dice display as their names, so expressions using them
+/// only parse back with the same `NamedDice`.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Expression {
-    exprs: Vec<Expr>,
+    /// Always a `Node::Sum`, even if of one operand.
+    root: Node,
+    pub(crate) label: Option<Arc<str>>,
 }
 impl Expression {
     pub(crate) fn new(exprs: Vec<Expr>) -> Self {
-        Expression { exprs }
+        Expression {
+            root: Node::Sum(exprs.into_iter().map(Node::Term).collect()),
+            label: None,
+        }
+    }
+    pub(crate) fn from_root(root: Node) -> Self {
+        Expression {
+            root: Node::Sum(root.into_operands()),
+            label: None,
+        }
+    }
+    /// Start assembling an expression term by term, rather than parsing one.
+    pub fn builder() -> ExpressionBuilder {
+        ExpressionBuilder::new()
+    }
+    /// What this expression is for, as the `Longsword` in `1d20+5 # Longsword`.
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+    pub(crate) fn root(&self) -> &Node {
+        &self.root
     }
     pub(crate) fn iter(&self) -> ExpressionRefIterator<'_> {
         ExpressionRefIterator {
-            internal_iterator: self.exprs.iter(),
+            internal_iterator: self.root.leaves().into_iter(),
         }
     }
     // This could be a trait implementation, but it's not supposed
     // to be visible outside of this crate.
     pub(crate) fn into_iter(self) -> ExpressionIterator {
         ExpressionIterator {
-            internal_iterator: self.exprs.into_iter(),
+            internal_iterator: self.root.leaves().into_iter().cloned().collect::<Vec<_>>().into_iter(),
         }
     }
-    pub fn terms(&self) -> TermIter {
+    /// Every term in this expression, from left to right,
+    /// regardless of how they're combined.
+    pub fn terms(&self) -> TermIter<'_> {
         TermIter { internal_iterator: self.iter() }
     }
     pub fn roll_with<R: ::rand::Rng>(&self, rng: &mut R) -> Result<crate::ExpressionResult, crate::Error> {
-        crate::roll_expr_iter_with(rng, self.iter().copied())
+        crate::roll_expression_with::<crate::Checked, _>(rng, self)
     }
     #[cfg(feature = "thread_rng")]
     pub fn roll(&self) -> crate::EResult {
         self.roll_with(&mut ::rand::thread_rng())
     }
+    /// The exact probability of every outcome of this expression, without rolling it.
+    ///
+    /// Fails if any possible outcome would overflow or divide by zero,
+    /// or if there are too many outcomes to work through.
+    pub fn distribution(&self) -> Result<crate::distribution::Distribution, crate::Error> {
+        crate::distribution::distribution(&self.root)
+    }
+    /// Lowest and highest totals this expression could possibly roll,
+    /// assuming explosions run as long as they're allowed to.
+    ///
+    /// Unlike `distribution`, this is cheap to compute,
+    /// but also fails if any part of the expression could overflow or divide by zero.
+    /// ```
+    /// # use mice::{parse::Expression, Error};
+    /// let (_, expression) = Expression::parse("2d6 - 1d4 * 2").unwrap();
+    /// assert_eq!(expression.unwrap().bounds()?, (-6, 10));
+    /// # Ok::<(), Error>(())
+    /// ```
+    pub fn bounds(&self) -> Result<(i64, i64), crate::Error> {
+        crate::distribution::bounds(&self.root)
+    }
+    /// Probability of this expression totalling at least `n`.
+    ///
+    /// ```
+    /// # use mice::{parse::Expression, Error};
+    /// let (_, expression) = Expression::parse("1d20 + 5").unwrap();
+    /// assert!((expression.unwrap().chance_at_least(15)? - 0.55).abs() < 1e-12);
+    /// # Ok::<(), Error>(())
+    /// ```
+    pub fn chance_at_least(&self, n: i64) -> Result<f64, crate::Error> {
+        Ok(self.distribution()?.chance(Comparison::GreaterOrEqual(n)))
+    }
+    /// Probability of this expression totalling at most `n`.
+    pub fn chance_at_most(&self, n: i64) -> Result<f64, crate::Error> {
+        Ok(self.distribution()?.chance(Comparison::LessOrEqual(n)))
+    }
+    /// Probability of this expression totalling exactly `n`.
+    pub fn chance_exactly(&self, n: i64) -> Result<f64, crate::Error> {
+        Ok(self.distribution()?.pmf(n))
+    }
+    /// Probability of this expression totalling more than `other`,
+    /// when both are rolled.
+    pub fn chance_greater_than(&self, other: &Expression) -> Result<f64, crate::Error> {
+        Ok(self.distribution()?.chance_greater(&other.distribution()?))
+    }
+    /// Merge like dice terms and fold constants together, so that
+    /// `1d6 + 1d6 + 2 + 3 - 1` becomes `2d6 + 4`, with dice first and constants last.
+    ///
+    /// Terms are only merged with others of the same sign and label,
+    /// and dice with keep modifiers are left alone, since `2d20kh1` isn't `1d20kh1 + 1d20kh1`.
+    /// Products and groups are kept as they are, but simplified inside.
+    /// ```
+    /// # use mice::parse::Expression;
+    /// let expression: Expression = "3 + 1d6 + 1d8 - 1 + 1d6".parse()?;
+    /// assert_eq!(expression.simplify().to_string(), "2d6 + 1d8 + 2");
+    /// assert_eq!(expression.simplify_in_order().to_string(), "2 + 2d6 + 1d8");
+    /// # Ok::<(), mice::ParseError>(())
+    /// ```
+    pub fn simplify(&self) -> Expression {
+        Expression {
+            root: Node::Sum(self.root.simplified(false).into_operands()),
+            label: self.label.clone(),
+        }
+    }
+    /// Simplify this expression like `simplify`, but leave merged terms
+    /// where the first of them was, rather than moving constants last.
+    pub fn simplify_in_order(&self) -> Expression {
+        Expression {
+            root: Node::Sum(self.root.simplified(true).into_operands()),
+            label: self.label.clone(),
+        }
+    }
     /// Nom parser for an `Expression`.
     ///
     /// This is the same as `parse::dice`,
@@ -187,8 +980,202 @@ impl Expression {
         dice(input)
     }
 }
+/// Whether two terms can be added together as one, like `1d6 + 1d6` as `2d6`.
+/// Keep modifiers apply to all of a term's dice at once, so terms with them can't be.
+fn mergeable(a: &Expr, b: &Expr) -> bool {
+    let alike = |x: &DiceTerm, y: &DiceTerm| {
+        x.keep.is_none() && DiceTerm { number: 0, ..x.clone() } == DiceTerm { number: 0, ..y.clone() }
+    };
+    a.sign == b.sign
+        && a.label == b.label
+        && match (&a.term, &b.term) {
+            (Term::Dice(x), Term::Dice(y)) | (Term::Percentile(x), Term::Percentile(y)) => alike(x, y),
+            (Term::Fate(_), Term::Fate(_)) => true,
+            _ => false,
+        }
+}
+
+/// Add `b`'s dice to `a`, if they're alike and their count doesn't overflow.
+fn merge(a: &mut Expr, b: &Expr) -> bool {
+    if !mergeable(a, b) {
+        return false;
+    }
+    let (count, more) = match (&mut a.term, &b.term) {
+        (Term::Dice(x), Term::Dice(y)) | (Term::Percentile(x), Term::Percentile(y)) => (&mut x.number, y.number),
+        (Term::Fate(x), Term::Fate(y)) => (&mut x.number, y.number),
+        _ => return false,
+    };
+    match count.checked_add(more) {
+        Some(n) => {
+            *count = n;
+            true
+        }
+        None => false,
+    }
+}
+
+/// Constant terms of the given value, split up if it doesn't fit in one.
+fn constants(mut value: i128, label: Option<Arc<str>>) -> Vec<Node> {
+    let mut terms = Vec::new();
+    while value != 0 {
+        let part = value.abs().min(i64::MAX as i128);
+        let sign = if value < 0 { Sign::Negative } else { Sign::Positive };
+        let term = Term::Constant(part as i64);
+        terms.push(Node::Term(Expr { term, sign, label: label.clone() }));
+        value -= sign * part;
+    }
+    terms
+}
+
+/// Merge like terms among the operands of a sum, and fold its constants,
+/// keeping those with different labels apart.
+fn simplify_sum(operands: Vec<Node>, preserve_order: bool) -> Vec<Node> {
+    // Constants are totalled in place of the first of each label.
+    let mut totals: Vec<(Option<Arc<str>>, usize, i128)> = Vec::new();
+    let mut merged: Vec<Node> = Vec::new();
+    for x in operands {
+        let t = match x {
+            Node::Term(t) => t,
+            x => {
+                merged.push(x);
+                continue;
+            }
+        };
+        if let Term::Constant(c) = t.term {
+            let value = t.sign * i128::from(c);
+            match totals.iter_mut().find(|(label, _, _)| *label == t.label) {
+                Some((_, _, total)) => *total += value,
+                None => {
+                    totals.push((t.label.clone(), merged.len(), value));
+                    merged.push(Node::Term(t));
+                }
+            }
+            continue;
+        }
+        let into = merged.iter_mut().any(|y| match y {
+            Node::Term(y) => merge(y, &t),
+            _ => false,
+        });
+        if !into {
+            merged.push(Node::Term(t));
+        }
+    }
+    let mut folded: Vec<Vec<Node>> = merged.into_iter().map(|x| vec![x]).collect();
+    for (label, slot, total) in totals {
+        folded[slot] = constants(total, label);
+    }
+    let is_constant = |x: &Vec<Node>| matches!(x.as_slice(), [] | [Node::Term(Expr { term: Term::Constant(_), .. }), ..]);
+    // A negated product can't lead a sum, since its sign would be read as part of the product.
+    let leads = |x: &[Vec<Node>]| !matches!(x.first().map(|x| &x[..]), Some([Node::Neg(x), ..]) if matches!(**x, Node::Product(..)));
+    if !preserve_order {
+        let mut sorted = folded.clone();
+        // Stable, so dice keep their order, as do constants.
+        sorted.sort_by_key(|x| is_constant(x));
+        if leads(&sorted) {
+            folded = sorted;
+        }
+    }
+    let operands: Vec<Node> = folded.into_iter().flatten().collect();
+    if operands.is_empty() {
+        vec![Node::Term(Expr { term: Term::Constant(0), sign: Sign::Positive, label: None })]
+    } else {
+        operands
+    }
+}
+
+/// A builder for an `Expression`, for assembling rolls without writing them out.
+/// Terms are added together in the order they're given.
+/// ```
+/// # use mice::parse::{Expression, Sign};
+/// let expression = Expression::builder()
+///     .dice(1, 20)?
+///     .constant(5)
+///     .sign(Sign::Negative)
+///     .dice(1, 4)?
+///     .build();
+/// assert_eq!(expression.to_string(), "1d20 + 5 - 1d4");
+/// # Ok::<(), mice::parse::InvalidDie>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct ExpressionBuilder {
+    terms: Vec<Expr>,
+    /// Sign of the next term added.
+    sign: Sign,
+}
+impl ExpressionBuilder {
+    pub fn new() -> Self {
+        ExpressionBuilder {
+            terms: Vec::new(),
+            sign: Sign::Positive,
+        }
+    }
+    /// Set the sign of the next term added, so that it's subtracted if negative.
+    /// Later terms are added again.
+    pub fn sign(mut self, sign: Sign) -> Self {
+        self.sign = sign;
+        self
+    }
+    fn push(&mut self, term: Term, sign: Sign) {
+        self.terms.push(Expr { term, sign: self.sign * sign, label: None });
+        self.sign = Sign::Positive;
+    }
+    /// Add `count` dice with `sides` sides each.
+    /// Fails if there are fewer than no dice, or fewer than one side.
+    pub fn dice(mut self, count: i64, sides: i64) -> Result<Self, InvalidDie> {
+        let die = DiceTerm::new(count, sides)?;
+        self.push(Term::Dice(die), Sign::Positive);
+        Ok(self)
+    }
+    /// Add a constant. Negative constants are subtracted.
+    pub fn constant(mut self, value: i64) -> Self {
+        let sign = if value < 0 { Sign::Negative } else { Sign::Positive };
+        match value.checked_abs() {
+            Some(x) => self.push(Term::Constant(x), sign),
+            None => {
+                // `i64::MIN` has no positive counterpart, so take it in two parts.
+                let sign = self.sign;
+                self.push(Term::Constant(i64::MAX), Sign::Negative);
+                self = self.sign(sign);
+                self.push(Term::Constant(1), Sign::Negative);
+            }
+        }
+        self
+    }
+    /// Finish the expression. One with no terms is a constant `0`.
+    pub fn build(self) -> Expression {
+        if self.terms.is_empty() {
+            return Expression::new(vec![Expr {
+                term: Term::Constant(0),
+                sign: Sign::Positive,
+                label: None,
+            }]);
+        }
+        Expression::new(self.terms)
+    }
+}
+impl Default for ExpressionBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Display for Expression {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        self.root.write_canonical(f)?;
+        if let Some(label) = &self.label {
+            write!(f, " # {}", label)?;
+        }
+        Ok(())
+    }
+}
+impl FromStr for Expression {
+    type Err = ParseError;
+    fn from_str(input: &str) -> Result<Self, ParseError> {
+        wrap_dice(input)
+    }
+}
 pub(crate) struct ExpressionRefIterator<'a> {
-    internal_iterator: ::std::slice::Iter<'a, Expr>,
+    internal_iterator: ::std::vec::IntoIter<&'a Expr>,
 }
 impl<'a> Iterator for ExpressionRefIterator<'a> {
     type Item = &'a Expr;
@@ -214,9 +1201,8 @@ impl<'a> Iterator for TermIter<'a> {
         self.internal_iterator.next().map(|x| &x.term)
     }
 }
-
 fn is_dec_digit(c: char) -> bool {
-    c.is_digit(10)
+    c.is_ascii_digit()
 }
 
 /// Parser for an effectively 63-bit unsigned integer.
@@ -243,8 +1229,9 @@ pub fn integer(input: &str) -> IResult<&str, i64> {
 #[error("invalid die")]
 pub struct InvalidDie;
 impl From<InvalidDie> for ParseError {
+    /// Without the input, the position of the die is unknown.
     fn from(_: InvalidDie) -> Self {
-        Self::InvalidExpression
+        Self::InvalidDie { offset: 0 }
     }
 }
 
@@ -252,9 +1239,6 @@ type PResult<I, O, PE, E = (I, ::nom::error::ErrorKind)> = Result<(I, Result<O,
 fn okay<I, T, PE, E>(input: I, exp: T) -> Result<(I, Result<T, PE>), E> {
     Ok((input, Ok(exp)))
 }
-fn purr<I, T, PE, E>(input: I, err: PE) -> Result<(I, Result<T, PE>), E> {
-    Ok((input, Err(err)))
-}
 macro_rules! trip {
     ($in:expr, $exp:expr) => {
         match $exp {
@@ -263,22 +1247,193 @@ macro_rules! trip {
         }
     }
 }
+/// Modifiers that may trail a dice term, such as `kh3`.
+enum Modifier {
+    Keep(Keep),
+    /// The threshold is left unset when it should default to
+    /// the highest face, which depends on the die.
+    Explode(ExplodeKind, Option<Comparison>),
+    Reroll(Reroll),
+    Target(Target),
+    CriticalSuccess(Comparison),
+    CriticalFailure(Comparison),
+}
+
+/// Parser for a comparison against a die face, like `>=5`.
+/// A bare number is an equality test.
+fn comparison(input: &str) -> IResult<&str, Comparison> {
+    let (input, (op, value)) = tuple((
+        opt(alt((tag(">="), tag("<="), tag(">"), tag("<"), tag("=")))),
+        integer,
+    ))(input)?;
+    Ok((
+        input,
+        match op {
+            Some(">=") => Comparison::GreaterOrEqual(value),
+            Some("<=") => Comparison::LessOrEqual(value),
+            Some(">") => Comparison::Greater(value),
+            Some("<") => Comparison::Less(value),
+            _ => Comparison::Equal(value),
+        },
+    ))
+}
+
+fn explode(input: &str) -> IResult<&str, (ExplodeKind, Option<Comparison>)> {
+    let (input, (kind, threshold)) = tuple((
+        alt((tag("!!"), tag("!p"), tag("!"))),
+        opt(comparison),
+    ))(input)?;
+    let kind = match kind {
+        "!!" => ExplodeKind::Compounding,
+        "!p" => ExplodeKind::Penetrating,
+        _ => ExplodeKind::Standard,
+    };
+    Ok((input, (kind, threshold)))
+}
+
+fn keep(input: &str) -> IResult<&str, Keep> {
+    let (input, (kind, count)) = tuple((
+        alt((tag("kh"), tag("kl"), tag("dh"), tag("dl"))),
+        opt(integer),
+    ))(input)?;
+    let count = count.unwrap_or(1);
+    Ok((
+        input,
+        match kind {
+            "kh" => Keep::Highest(count),
+            "kl" => Keep::Lowest(count),
+            "dh" => Keep::DropHighest(count),
+            _ => Keep::DropLowest(count),
+        },
+    ))
+}
+
+fn reroll(input: &str) -> IResult<&str, Reroll> {
+    let (input, (kind, condition)) = tuple((alt((tag("ro"), tag("r"))), opt(comparison)))(input)?;
+    Ok((
+        input,
+        Reroll {
+            once: kind == "ro",
+            condition: condition.unwrap_or(Comparison::Equal(1)),
+        },
+    ))
+}
+
+/// Success targets need an explicit comparison operator,
+/// so `10d10>=8` and `10d10=10`, but not `10d10 10`.
+fn target(input: &str) -> IResult<&str, Target> {
+    let (input, (_, success, failure)) = tuple((
+        peek(alt((tag(">"), tag("<"), tag("=")))),
+        comparison,
+        opt(tuple((tag("f"), comparison))),
+    ))(input)?;
+    Ok((
+        input,
+        Target {
+            success,
+            failure: failure.map(|(_, x)| x),
+        },
+    ))
+}
+
+/// Crit ranges, like `cs>19` or `cf1`.
+fn critical(input: &str) -> IResult<&str, Modifier> {
+    let (input, (kind, test)) = tuple((alt((tag("cs"), tag("cf"))), comparison))(input)?;
+    Ok((
+        input,
+        match kind {
+            "cs" => Modifier::CriticalSuccess(test),
+            _ => Modifier::CriticalFailure(test),
+        },
+    ))
+}
+
+fn modifier(input: &str) -> IResult<&str, Modifier> {
+    alt((
+        map(keep, Modifier::Keep),
+        map(explode, |(k, t)| Modifier::Explode(k, t)),
+        map(reroll, Modifier::Reroll),
+        map(target, Modifier::Target),
+        critical,
+    ))(input)
+}
+
 fn die(input: &str) -> PResult<&str, DiceTerm, InvalidDie> {
     // number of dice : [integer]
     // separator      : "d"
     // size of dice   : integer
+    // modifiers      : modifier*
     let (input, (number, _, size)) = tuple((opt(integer), tag("d"), integer))(input)?;
     let number = number.unwrap_or(1);
-    // Note that since we use the bare DiceTerm constructor,
-    // we need to make certain no invalid dice are created.
-    // That means `number` needs to be >= 0, and `size` needs to be >= 1.
-    // Given that `integer` does not create integers less than zero,
-    // the only check we need to do here is `size != 0`.
-    if size != 0 {
-        okay(input, DiceTerm { number, size })
-    } else {
-        purr(input, InvalidDie)
+    let die = trip!(input, DiceTerm::new(number, size));
+    let (input, die) = modifiers(input, die)?;
+    okay(input, die)
+}
+
+fn modifiers(mut input: &str, mut die: DiceTerm) -> IResult<&str, DiceTerm> {
+    // Each kind of modifier may appear at most once, in any order.
+    // A repeated modifier is left unconsumed, and so rejected as trailing input.
+    loop {
+        let (rest, m) = match modifier(input) {
+            Ok(x) => x,
+            Err(Failure(e)) => return Err(Failure(e)),
+            Err(_) => break,
+        };
+        if !die.modify(m) {
+            break;
+        }
+        input = rest;
     }
+    Ok((input, die))
+}
+
+/// A face of a custom die, which unlike other numbers may be negative.
+fn face(input: &str) -> IResult<&str, i64> {
+    let (input, (sign, value)) = tuple((opt(sign), integer))(input)?;
+    Ok((input, sign.unwrap_or(Sign::Positive) * value))
+}
+
+/// `{1,1,2,3,5,8}`, or `{-1,0,1}`
+fn face_list(input: &str) -> IResult<&str, Vec<i64>> {
+    let ws = |x| many0(whitespace)(x);
+    let (input, (_, _, first, rest, _, _)) = tuple((
+        tag("{"),
+        ws,
+        face,
+        many0(map(tuple((ws, tag(","), ws, face)), |(_, _, _, x)| x)),
+        ws,
+        tag("}"),
+    ))(input)?;
+    let mut faces = vec![first];
+    faces.extend(rest);
+    Ok((input, faces))
+}
+
+fn custom_die<'a>(input: &'a str, named: &NamedDice) -> PResult<&'a str, DiceTerm, InvalidDie> {
+    let (input, (number, _)) = tuple((opt(integer), tag("d")))(input)?;
+    let (input, faces) = match face_list(input) {
+        Ok((input, values)) => (input, Faces { name: None, values: trip!(input, checked_faces(values)) }),
+        Err(Failure(e)) => return Err(Failure(e)),
+        Err(_) => match named.longest_prefix(input) {
+            Some((name, faces)) => (&input[name.len()..], faces.clone()),
+            None => return Err(::nom::Err::Error((input, ::nom::error::ErrorKind::Tag))),
+        },
+    };
+    let die = trip!(input, DiceTerm::with_faces(number.unwrap_or(1), faces));
+    let (input, die) = modifiers(input, die)?;
+    okay(input, die)
+}
+
+fn percentile(input: &str) -> PResult<&str, DiceTerm, InvalidDie> {
+    let (input, (number, _)) = tuple((opt(integer), tag("d%")))(input)?;
+    let die = trip!(input, DiceTerm::new(number.unwrap_or(1), 100));
+    let (input, die) = modifiers(input, die)?;
+    okay(input, die)
+}
+
+fn fate(input: &str) -> IResult<&str, FateTerm> {
+    let (input, (number, _)) = tuple((opt(integer), tag("dF")))(input)?;
+    Ok((input, FateTerm { number: number.unwrap_or(1) }))
 }
 
 fn addition(input: &str) -> IResult<&str, Sign> {
@@ -290,17 +1445,10 @@ fn subtraction(input: &str) -> IResult<&str, Sign> {
     Ok((input, Sign::Negative))
 }
 
-fn operator(input: &str) -> IResult<&str, Sign> {
-    alt((addition, subtraction))(input)
-}
-
 /// Parser for a `+` or `-` sign.
 pub fn sign(input: &str) -> IResult<&str, Sign> {
-    // While currently equivalent to `operator`,
-    // the idea of a sign will never change, whilst `operator`
-    // may be extended to recognize other operators,
-    // which would break someone who just wants
-    // a positive or negative sign.
+    // The idea of a sign will never change, whilst
+    // the set of binary operators has grown beyond it.
     // TL;DR: Arithmetic != Sign
     alt((addition, subtraction))(input)
 }
@@ -310,8 +1458,54 @@ pub fn whitespace(input: &str) -> IResult<&str, &str> {
     alt((tag(" "), tag("\t")))(input)
 }
 
-fn separator(input: &str) -> IResult<&str, Sign> {
-    tuple((many0(whitespace), operator, many0(whitespace)))(input).map(|(i, (_, op, _))| (i, op))
+/// Binary operators, in order of increasing precedence.
+#[derive(Debug, Copy, Clone)]
+enum BinaryOperator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+impl BinaryOperator {
+    fn precedence(self) -> u8 {
+        match self {
+            BinaryOperator::Add | BinaryOperator::Subtract => 1,
+            BinaryOperator::Multiply | BinaryOperator::Divide => 2,
+        }
+    }
+    fn is_product(self) -> bool {
+        self.precedence() == 2
+    }
+    fn apply(self, lhs: Node, rhs: Node) -> Node {
+        let sum = |lhs: Node, rhs| {
+            let mut operands = lhs.into_operands();
+            operands.push(rhs);
+            Node::Sum(operands)
+        };
+        match self {
+            BinaryOperator::Add => sum(lhs, rhs),
+            BinaryOperator::Subtract => sum(lhs, rhs.signed(Sign::Negative)),
+            BinaryOperator::Multiply => Node::Product(Box::new(lhs), Operator::Multiply, Box::new(rhs)),
+            BinaryOperator::Divide => Node::Product(Box::new(lhs), Operator::Divide, Box::new(rhs)),
+        }
+    }
+}
+
+fn binary_operator(input: &str) -> IResult<&str, BinaryOperator> {
+    let (input, (_, op, _)) = tuple((
+        many0(whitespace),
+        alt((tag("+"), tag("-"), tag("*"), tag("/"))),
+        many0(whitespace),
+    ))(input)?;
+    Ok((
+        input,
+        match op {
+            "+" => BinaryOperator::Add,
+            "-" => BinaryOperator::Subtract,
+            "*" => BinaryOperator::Multiply,
+            _ => BinaryOperator::Divide,
+        },
+    ))
 }
 
 fn constant(input: &str) -> IResult<&str, ConstantTerm> {
@@ -325,37 +1519,327 @@ fn constant(input: &str) -> IResult<&str, ConstantTerm> {
 //     Ok((input, Term::Constant(v)))
 // }
 
-fn term(input: &str) -> PResult<&str, Term, InvalidDie> {
+fn term<'a>(input: &'a str, named: &NamedDice) -> PResult<&'a str, Term, InvalidDie> {
     alt((
+        |x| custom_die(x, named).map(|(i, d)| (i, d.map(Term::Dice))),
+        |x| fate(x).map(|(i, d)| (i, Ok(Term::Fate(d)))),
+        |x| percentile(x).map(|(i, d)| (i, d.map(Term::Percentile))),
         |x| die(x).map(|(i, d)| (i, d.map(Term::Dice))),
         |x| constant(x).map(|(i, c)| (i, Ok(Term::Constant(c.value)))),
     ))(input)
 }
 
+/// A label on a single term, the `fire` in `2d6[fire]`.
+fn term_label(input: &str) -> IResult<&str, &str> {
+    let (input, (_, label, _)) = tuple((tag("["), take_while1(|c| c != '[' && c != ']'), tag("]")))(input)?;
+    Ok((input, label))
+}
+
+/// Nom parser for a label trailing a whole expression, written as a comment,
+/// like the `# Longsword` in `1d20+5 # Longsword`.
+///
+/// Labels end at `;` or `,`, so that each expression in a list may have one.
+pub fn label(input: &str) -> IResult<&str, &str> {
+    let text = take_till(|c| c == ';' || c == ',');
+    let (input, (_, _, _, label)) = tuple((many0(whitespace), tag("#"), many0(whitespace), text))(input)?;
+    Ok((input, label.trim_end()))
+}
+
+/// The error kind of the `Failure` for going past `NESTING_LIMIT`.
+const TOO_DEEP: ErrorKind = ErrorKind::Verify;
+
+/// Go one level deeper from `depth`, failing at `input` past `NESTING_LIMIT`.
+fn nest(input: &str, depth: usize) -> Result<usize, ::nom::Err<(&str, ErrorKind)>> {
+    if depth < NESTING_LIMIT {
+        Ok(depth + 1)
+    } else {
+        Err(Failure((input, TOO_DEEP)))
+    }
+}
+
+/// A parenthesized expression, optionally preceded by a function name.
+fn group<'a, 'b>(
+    name: &'static str,
+    wrap: fn(Box<Node>) -> Node,
+    depth: usize,
+    named: &'b NamedDice,
+) -> impl Fn(&'a str) -> PResult<&'a str, Node, InvalidDie> + 'b {
+    move |start| {
+        let (input, _) = tuple((tag(name), tag("(")))(start)?;
+        let depth = nest(start, depth)?;
+        let (input, (_, inner)) = tuple((many0(whitespace), |x| climb(x, 1, true, depth, named)))(input)?;
+        let inner = trip!(input, inner);
+        let (input, _) = tuple((many0(whitespace), tag(")")))(input)?;
+        okay(input, wrap(Box::new(inner)))
+    }
+}
+
+fn atom<'a>(input: &'a str, depth: usize, named: &NamedDice) -> PResult<&'a str, Node, InvalidDie> {
+    alt((
+        group("", Node::Group, depth, named),
+        group("floor", Node::Floor, depth, named),
+        |x| {
+            let (input, (term, label)) = tuple((|x| term(x, named), opt(term_label)))(x)?;
+            let term = trip!(input, term);
+            let label = label.map(Arc::from);
+            okay(input, Node::Term(Expr { term, sign: Sign::Positive, label }))
+        },
+    ))(input)
+}
+
+/// An atom, with an optional sign if `signed`, as in `-3` or `-(2d6)`.
+fn factor<'a>(input: &'a str, signed: bool, depth: usize, named: &NamedDice) -> PResult<&'a str, Node, InvalidDie> {
+    let (input, sign) = match signed {
+        true => opt(tuple((sign, many0(whitespace))))(input)?,
+        false => (input, None),
+    };
+    let (input, atom) = atom(input, depth, named)?;
+    let atom = trip!(input, atom);
+    okay(input, atom.signed(sign.map(|(s, _)| s).unwrap_or(Sign::Positive)))
+}
+
+/// Precedence climbing over `factor`s separated by binary operators,
+/// only consuming operators of at least `min_precedence`.
+/// The first factor may have a sign if `signed`, as at the start of a group.
+fn climb<'a>(
+    input: &'a str,
+    min_precedence: u8,
+    signed: bool,
+    mut depth: usize,
+    named: &NamedDice,
+) -> PResult<&'a str, Node, InvalidDie> {
+    let (mut input, lhs) = factor(input, signed, depth, named)?;
+    let mut lhs = trip!(input, lhs);
+    loop {
+        let (rest, op) = match binary_operator(input) {
+            Ok((rest, op)) if op.precedence() >= min_precedence => (rest, op),
+            Ok(_) | Err(::nom::Err::Error(_)) => break,
+            Err(e) => return Err(e),
+        };
+        // Products nest on the left, sums stay flat.
+        if op.is_product() {
+            depth = nest(input.trim_start_matches(&[' ', '\t'][..]), depth)?;
+        }
+        // Operators are all left associative,
+        // so the right hand side only takes tighter binding ones.
+        // Only a product's may have a sign, since `2 + -3` is just `2 - 3`.
+        let (rest, rhs) = match climb(rest, op.precedence() + 1, op.is_product(), depth + 1, named) {
+            Ok(x) => x,
+            Err(::nom::Err::Error(_)) => break,
+            Err(e) => return Err(e),
+        };
+        input = rest;
+        lhs = op.apply(lhs, trip!(input, rhs));
+    }
+    okay(input, lhs)
+}
+
 /// Nom parser for a dice expression.
 pub fn dice(input: &str) -> PResult<&str, Expression, InvalidDie> {
-    // [(+/-)] dice ((+/-) dice)*
-    let (input, (sign, term, terms)) =
-        tuple((opt(separator), term, many0(tuple((separator, term)))))(input)?;
-    let sign = sign.unwrap_or(Sign::Positive);
-    let term = trip!(input, term);
-    let mut expression = vec![Expr { term, sign }];
-    for (sign, term) in terms {
-        expression.push(Expr { term: trip!(input, term), sign })
+    dice_with(input, &NamedDice::new())
+}
+
+/// Nom parser for a dice expression which may use the given named dice.
+pub fn dice_with<'a>(input: &'a str, named: &NamedDice) -> PResult<&'a str, Expression, InvalidDie> {
+    // [(+/-)] operand (operator operand)*
+    // where operands are terms, or parenthesized dice expressions.
+    let (input, (_, root)) = tuple((many0(whitespace), |x| climb(x, 1, true, 0, named)))(input)?;
+    let root = trip!(input, root);
+    let (input, label) = opt(label)(input)?;
+    let mut expression = Expression::from_root(root);
+    expression.label = label.filter(|x| !x.is_empty()).map(Arc::from);
+    okay(input, expression)
+}
+
+/// Nom parser for several dice expressions separated by `;` or `,`,
+/// as in `1d20+5; 2d6+3`.
+pub fn dice_list(input: &str) -> PResult<&str, Vec<Expression>, InvalidDie> {
+    dice_list_with(input, &NamedDice::new())
+}
+
+/// Nom parser for a list of dice expressions which may use the given named dice.
+pub fn dice_list_with<'a>(input: &'a str, named: &NamedDice) -> PResult<&'a str, Vec<Expression>, InvalidDie> {
+    let separator = tuple((many0(whitespace), alt((tag(";"), tag(","))), many0(whitespace)));
+    let (mut input, first) = dice_with(input, named)?;
+    let mut expressions = vec![trip!(input, first)];
+    while let Ok((rest, _)) = separator(input) {
+        let (rest, next) = match dice_with(rest, named) {
+            Ok(x) => x,
+            Err(::nom::Err::Error(_)) => break,
+            Err(e) => return Err(e),
+        };
+        expressions.push(trip!(rest, next));
+        input = rest;
     }
-    okay(input, Expression::new(expression))
+    okay(input, expressions)
 }
 
-/// Wrap up getting errors from parsing a dice expression.
-pub(crate) fn wrap_dice(input: &str) -> Result<Expression, ParseError> {
-    let (input, e) = match dice(input.trim()) {
-        Ok(x) => x,
-        Err(_) => return Err(ParseError::InvalidExpression),
+/// Nom parser for the repetition prefix of a batch roll,
+/// the `12x` in `12x 1d20+3`, or the `6#` in `6#4d6kh3`.
+pub fn repetitions(input: &str) -> IResult<&str, i64> {
+    let (input, (count, _)) = tuple((integer, alt((tag("x"), tag("#")))))(input)?;
+    Ok((input, count))
+}
+
+/// Nom parser for a dice expression with an optional repetition prefix,
+/// giving the number of times to roll it.
+/// Expressions without a prefix are rolled once.
+pub fn batch(input: &str) -> PResult<&str, (i64, Expression), InvalidDie> {
+    batch_with(input, &NamedDice::new())
+}
+
+/// Nom parser for a batch roll which may use the given named dice.
+pub fn batch_with<'a>(input: &'a str, named: &NamedDice) -> PResult<&'a str, (i64, Expression), InvalidDie> {
+    let (input, count) = opt(repetitions)(input)?;
+    let (input, expression) = dice_with(input, named)?;
+    let expression = trip!(input, expression);
+    okay(input, (count.unwrap_or(1), expression))
+}
+
+/// Where parsing may continue after an operand, besides at a binary operator.
+#[derive(Copy, Clone, PartialEq)]
+enum Context {
+    Expression,
+    List,
+    Group,
+}
+
+/// Find where an operand was expected but couldn't be parsed,
+/// and what was wanted there. It may have a sign if `signed`.
+fn expect_factor<'a>(input: &'a str, signed: bool, named: &NamedDice) -> (&'a str, Expected) {
+    let ws = |x| many0(whitespace)(x);
+    let (input, _) = match signed {
+        true => opt(tuple((sign, ws)))(input).unwrap_or((input, None)),
+        false => (input, None),
     };
-    // Prevent weirdness like "10dlol" => 10
-    if !input.is_empty() {
-        Err(ParseError::InvalidExpression)
-    } else {
-        e.map_err(|e| e.into())
+    if let Ok((inner, _)) = tuple((alt((tag("floor("), tag("("))), ws))(input) {
+        // Anything nested too deeply was refused before diagnosis.
+        return match climb(inner, 1, true, 0, named) {
+            Ok((rest, Ok(_))) => {
+                // Within a group, something is always expected.
+                let (at, expected) = expect_after(inner, rest, Context::Group, named);
+                (at, expected.unwrap_or(Expected::Parenthesis))
+            }
+            Ok((rest, Err(_))) => (rest, Expected::Number),
+            Err(_) => expect_factor(inner, true, named),
+        };
+    }
+    match input.strip_prefix('d') {
+        Some(size) => (size, Expected::Number),
+        None => (input, Expected::Number),
+    }
+}
+
+/// Find what went wrong after an operand which was parsed successfully,
+/// ending at `rest`, given the `input` it started in.
+/// Nothing was expected if the operand could have ended the input.
+fn expect_after<'a>(
+    input: &'a str,
+    rest: &'a str,
+    context: Context,
+    named: &NamedDice,
+) -> (&'a str, Option<Expected>) {
+    if let Ok((after, op)) = binary_operator(rest) {
+        let (at, expected) = expect_factor(after, op.is_product(), named);
+        return (at, Some(expected));
     }
+    let next = rest.trim_start_matches(&[' ', '\t'][..]);
+    if context == Context::List {
+        if let Ok((after, _)) = tuple((alt((tag(";"), tag(","))), many0(whitespace)))(next) {
+            let (at, expected) = expect_factor(after, true, named);
+            return (at, Some(expected));
+        }
+    }
+    // A constant followed directly by a `d`, or a letter and a number,
+    // like `2d` or `2x6`, was probably meant to be a dice term.
+    let before = &input[..input.offset(rest)];
+    let digits = before.len() - before.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+    let constant = digits > 0 && !before[..before.len() - digits].ends_with(|c: char| c.is_alphanumeric() || c == '}');
+    if constant && next.len() == rest.len() {
+        let mut chars = next.chars();
+        match (chars.next(), chars.next()) {
+            (Some('d'), _) => return (&next[1..], Some(Expected::Number)),
+            (Some(c), Some(n)) if c.is_alphabetic() && n.is_ascii_digit() => return (next, Some(Expected::Die)),
+            _ => (),
+        }
+    }
+    match context {
+        Context::Group => (next, Some(Expected::Parenthesis)),
+        _ if next.starts_with(|c: char| c.is_ascii_digit() || c == '(') => (next, Some(Expected::Operator)),
+        _ => (next, None),
+    }
+}
+
+/// Require a parser to have used up all of `input`, from `start`,
+/// turning how it failed into a `ParseError` if not.
+fn finish<'a, T>(
+    input: &'a str,
+    start: &'a str,
+    parsed: PResult<&'a str, T, InvalidDie>,
+    context: Context,
+    named: &NamedDice,
+) -> Result<T, ParseError> {
+    let (rest, expected) = match parsed {
+        // Prevent weirdness like "10dlol" => 10
+        Ok(("", Ok(x))) => return Ok(x),
+        Ok((rest, Ok(_))) => expect_after(start, rest, context, named),
+        Ok((rest, Err(InvalidDie))) => {
+            // Dice are refused just after their size, so find the `NdS` before that.
+            let before = &input[..input.offset(rest)];
+            let size = before.trim_end_matches(|c: char| c.is_ascii_digit());
+            let number = size.strip_suffix('d').unwrap_or(size);
+            let offset = number.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+            return Err(ParseError::InvalidDie { offset });
+        }
+        Err(Failure((rest, TOO_DEEP))) => return Err(ParseError::TooDeep { offset: input.offset(rest) }),
+        Err(Failure((rest, _))) => {
+            // Numbers too large for `i64` fail just after their digits.
+            let before = &input[..input.offset(rest)];
+            let offset = before.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+            return Err(ParseError::TooLarge { offset });
+        }
+        Err(_) => {
+            let (rest, expected) = expect_factor(start, true, named);
+            (rest, Some(expected))
+        }
+    };
+    let offset = input.offset(rest);
+    Err(match expected {
+        Some(expected) => ParseError::InvalidExpression { offset, expected },
+        None => ParseError::TrailingInput {
+            offset,
+            column: input[..offset].chars().count() + 1,
+        },
+    })
+}
+
+/// Wrap up getting errors from parsing a dice expression.
+pub(crate) fn wrap_dice(input: &str) -> Result<Expression, ParseError> {
+    wrap_dice_with(input, &NamedDice::new())
+}
+
+/// Wrap up getting errors from parsing a list of dice expressions.
+#[cfg(feature = "thread_rng")]
+pub(crate) fn wrap_dice_list(input: &str) -> Result<Vec<Expression>, ParseError> {
+    let (start, named) = (input.trim(), NamedDice::new());
+    finish(input, start, dice_list_with(start, &named), Context::List, &named)
+}
+
+/// Wrap up getting errors from parsing a batch roll.
+#[cfg(feature = "thread_rng")]
+pub(crate) fn wrap_batch(input: &str) -> Result<(i64, Expression), ParseError> {
+    wrap_batch_with(input, &NamedDice::new())
+}
+
+#[cfg(feature = "thread_rng")]
+pub(crate) fn wrap_batch_with(input: &str, named: &NamedDice) -> Result<(i64, Expression), ParseError> {
+    let trimmed = input.trim();
+    // Diagnose errors from after the prefix, which can't be at fault.
+    let (start, _) = opt(repetitions)(trimmed).unwrap_or((trimmed, None));
+    finish(input, start, batch_with(trimmed, named), Context::Expression, named)
+}
+
+pub(crate) fn wrap_dice_with(input: &str, named: &NamedDice) -> Result<Expression, ParseError> {
+    let start = input.trim();
+    finish(input, start, dice_with(start, named), Context::Expression, named)
 }
@@ -1,8 +1,10 @@
+use crate::builder::Replay;
 use crate::error::Error;
-use crate::parse::{Expr, Sign};
+use crate::fair::Fairness;
+use crate::parse::{Expr, Node, Operator, Sign, Term};
 use std::fmt::{Display, Formatter};
 use std::ops::Neg;
-use std::slice;
+use std::sync::Arc;
 #[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
@@ -13,8 +15,13 @@ pub(crate) type EResult = Result<ExpressionResult, Error>;
 #[derive(Debug, Clone)]
 pub struct ExpressionResult {
     /// Private field because `Expr`'s layout isn't final.
-    pairs: Vec<(Expr, EvaluatedTerm)>,
+    tree: Node<(Expr, EvaluatedTerm)>,
     total: i64,
+    label: Option<Arc<str>>,
+    /// Present on results of replayable rolls.
+    replay: Option<Replay>,
+    /// Present on results of fair rolls.
+    fairness: Option<Fairness>,
 }
 #[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl ExpressionResult {
@@ -26,8 +33,8 @@ impl ExpressionResult {
     pub fn display(&self) -> String {
         format!("{}", self)
     }
-    pub(crate) fn pairs(&self) -> &Vec<(Expr, EvaluatedTerm)> {
-        &self.pairs
+    pub(crate) fn tree(&self) -> &Node<(Expr, EvaluatedTerm)> {
+        &self.tree
     }
     // TODO: Bind *this* for wasm?
     /// Afford some control of the output to the user,
@@ -37,8 +44,115 @@ impl ExpressionResult {
     }
 }
 impl ExpressionResult {
-    pub(crate) fn new(pairs: Vec<(Expr, EvaluatedTerm)>, total: i64) -> Self {
-        Self { pairs, total }
+    /// The label of the expression rolled, if it had one.
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+    /// The seed and expression this was rolled with, for results of
+    /// `builder::ReplayableRoll`, which `builder::replay` rolls again identically.
+    pub fn replay(&self) -> Option<&Replay> {
+        self.replay.as_ref()
+    }
+    /// The commitment, client seed and nonce this was rolled with,
+    /// for results of `fair::FairRoll`, which `fair::verify` checks.
+    pub fn fairness(&self) -> Option<&Fairness> {
+        self.fairness.as_ref()
+    }
+    /// The parts of this result added together, each with the label of the terms in it.
+    ///
+    /// Sums are split up within groups and negations, and when multiplied
+    /// by something unlabeled, so `(1d6[fire] + 10) * 2` is fire and unlabeled parts.
+    /// Parts which can't be split, with terms of more than one label or of
+    /// none, like `1d6[fire] * 1d6[cold]` or `(1d6[fire] + 1) / 2`, are treated as unlabeled.
+    fn labeled_operands(&self) -> Result<Vec<(Option<&str>, i64)>, Error> {
+        let mut parts = Vec::new();
+        labeled_parts(&self.tree, &mut parts)?;
+        Ok(parts)
+    }
+    /// Every term rolled, from left to right,
+    /// regardless of how they were combined.
+    /// ```
+    /// # use mice::{roll, Error};
+    /// let result = roll("1d20 + 5")?;
+    /// let natural = result.terms().next().unwrap().dice().next().unwrap().face();
+    /// assert!((1..=20).contains(&natural));
+    /// # Ok::<(), Error>(())
+    /// ```
+    pub fn terms(&self) -> impl Iterator<Item = TermResult<'_>> {
+        self.tree
+            .leaves()
+            .into_iter()
+            .map(|(expr, evaluated)| TermResult { expr, evaluated })
+    }
+    /// Number of kept dice that rolled a critical success or failure, respectively.
+    /// Dropped dice don't count, so a natural 20 left out of `2d20kl1` isn't a crit.
+    ///
+    /// Concise results of `Roll::slim_roll` keep no dice, so they always count `(0, 0)`.
+    pub fn criticals(&self) -> (usize, usize) {
+        let dice = self.terms().flat_map(|x| x.dice());
+        dice.filter(|x| x.kept()).fold((0, 0), |(s, f), x| match x.critical() {
+            Some(Critical::Success) => (s + 1, f),
+            Some(Critical::Failure) => (s, f + 1),
+            None => (s, f),
+        })
+    }
+    /// Totals of the terms sharing each label, like damage types in `2d6[fire] + 1d4[cold] + 3`,
+    /// in the order each label first appears. Unlabeled terms are grouped under `None`.
+    /// ```
+    /// # use mice::{roll, Error};
+    /// let result = roll("2d1[fire] + 3 + 1d1[cold] - 1[fire]")?;
+    /// assert_eq!(result.subtotals()?, vec![(Some("fire"), 1), (None, 3), (Some("cold"), 1)]);
+    /// # Ok::<(), Error>(())
+    /// ```
+    pub fn subtotals(&self) -> Result<Vec<(Option<&str>, i64)>, Error> {
+        let mut subtotals: Vec<(Option<&str>, i64)> = Vec::new();
+        for (label, value) in self.labeled_operands()? {
+            match subtotals.iter_mut().find(|(x, _)| *x == label) {
+                Some((_, total)) => *total = crate::checked_add(*total, value)?,
+                None => subtotals.push((label, value)),
+            }
+        }
+        Ok(subtotals)
+    }
+    /// Recompute the total after transforming the subtotal of each label,
+    /// as for resistances and vulnerabilities to damage types.
+    /// ```
+    /// # use mice::{roll, Error, Transform};
+    /// let result = roll("5d1[fire] + 4[cold] + 3")?;
+    /// let total = result.transformed(|label| match label {
+    ///     Some("fire") => Transform::Halve,
+    ///     Some("cold") => Transform::Zero,
+    ///     _ => Transform::Keep,
+    /// })?;
+    /// assert_eq!(total, 2 + 0 + 3);
+    /// # Ok::<(), Error>(())
+    /// ```
+    pub fn transformed<F>(&self, transform: F) -> Result<i64, Error>
+    where
+        F: Fn(Option<&str>) -> Transform,
+    {
+        let mut total: i64 = 0;
+        for (label, subtotal) in self.subtotals()? {
+            total = crate::checked_add(total, transform(label).apply(subtotal)?)?;
+        }
+        Ok(total)
+    }
+    pub(crate) fn new(tree: Node<(Expr, EvaluatedTerm)>, total: i64, label: Option<Arc<str>>) -> Self {
+        Self {
+            tree,
+            total,
+            label,
+            replay: None,
+            fairness: None,
+        }
+    }
+    pub(crate) fn with_replay(mut self, replay: Replay) -> Self {
+        self.replay = Some(replay);
+        self
+    }
+    pub(crate) fn with_fairness(mut self, fairness: Fairness) -> Self {
+        self.fairness = Some(fairness);
+        self
     }
 }
 
@@ -48,6 +162,212 @@ impl Display for ExpressionResult {
     }
 }
 
+/// The results of rolling one expression several times, as with `12x 1d20+3`.
+#[derive(Debug, Clone)]
+pub struct BatchResult {
+    results: Vec<ExpressionResult>,
+}
+impl BatchResult {
+    pub(crate) fn new(results: Vec<ExpressionResult>) -> Self {
+        Self { results }
+    }
+    /// Every roll, in the order they were made.
+    pub fn results(&self) -> &[ExpressionResult] {
+        &self.results
+    }
+    pub fn into_results(self) -> Vec<ExpressionResult> {
+        self.results
+    }
+    /// The total of every roll, in the order they were made.
+    pub fn totals(&self) -> impl Iterator<Item = i64> + '_ {
+        self.results.iter().map(ExpressionResult::total)
+    }
+    /// Format every roll as a single list.
+    /// See `FormatOptions::concise` for listing only totals.
+    pub fn format(&self, options: FormatOptions) -> String {
+        crate::display::format_batch(&self.results, options)
+    }
+}
+impl Display for BatchResult {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.format(FormatOptions::new()))
+    }
+}
+
+/// The results of rolling several expressions together, as with `1d20+5; 2d6+3`.
+#[derive(Debug, Clone)]
+pub struct MultiResult {
+    results: Vec<ExpressionResult>,
+}
+impl MultiResult {
+    pub(crate) fn new(results: Vec<ExpressionResult>) -> Self {
+        Self { results }
+    }
+    /// The result of each expression, in the order they were written.
+    pub fn results(&self) -> &[ExpressionResult] {
+        &self.results
+    }
+    pub fn into_results(self) -> Vec<ExpressionResult> {
+        self.results
+    }
+    /// Format every result side by side, each as it would be on its own.
+    pub fn format(&self, options: FormatOptions) -> String {
+        crate::display::format_multi(&self.results, options)
+    }
+}
+impl Display for MultiResult {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.format(FormatOptions::new()))
+    }
+}
+
+/// A view of a single evaluated term of an `ExpressionResult`.
+#[derive(Debug, Clone, Copy)]
+pub struct TermResult<'a> {
+    expr: &'a Expr,
+    evaluated: &'a EvaluatedTerm,
+}
+impl<'a> TermResult<'a> {
+    /// The term as it was written, without its sign.
+    pub fn term(&self) -> &'a Term {
+        &self.expr.term
+    }
+    pub fn sign(&self) -> Sign {
+        self.expr.sign
+    }
+    /// The label of the term, as the `fire` in `2d6[fire]`.
+    pub fn label(&self) -> Option<&'a str> {
+        self.expr.label.as_deref()
+    }
+    /// The value of the term, with its sign applied.
+    pub fn value(&self) -> i64 {
+        self.evaluated.value()
+    }
+    /// Every die rolled for this term, in the order they were rolled.
+    /// Constant terms have none, and neither do results of slim rolls
+    /// formatted without them.
+    pub fn dice(&self) -> impl Iterator<Item = DieResult<'a>> {
+        let parts = match self.evaluated {
+            EvaluatedTerm::Die(x) => x.parts.as_slice(),
+            EvaluatedTerm::Constant(_) => &[],
+        };
+        parts.iter().map(|roll| DieResult { roll })
+    }
+}
+
+/// A view of a single die rolled for a term.
+#[derive(Debug, Clone, Copy)]
+pub struct DieResult<'a> {
+    roll: &'a DieRoll,
+}
+impl<'a> DieResult<'a> {
+    /// The face the die landed on, after any rerolls.
+    /// Fate dice show `-1`, `0` or `1`.
+    pub fn face(&self) -> i64 {
+        self.roll.face
+    }
+    /// Faces replaced by rerolls, in the order they were rolled.
+    pub fn rerolled(&self) -> &'a [i64] {
+        &self.roll.rerolled
+    }
+    /// Extra rolls from explosions, in the order they were rolled,
+    /// already reduced by one for penetrating dice.
+    pub fn explosions(&self) -> &'a [i64] {
+        &self.roll.chain
+    }
+    /// The face together with its explosions.
+    pub fn value(&self) -> i64 {
+        self.roll.value
+    }
+    /// Whether this die counts towards the total of its term.
+    /// Dice are only discarded by keep or drop modifiers.
+    pub fn kept(&self) -> bool {
+        self.roll.kept
+    }
+    /// How this die counted, for kept dice in terms that count successes.
+    pub fn outcome(&self) -> Option<Outcome> {
+        self.roll.outcome
+    }
+    /// Whether the face, after rerolls, fell in a crit range of its term.
+    pub fn critical(&self) -> Option<Critical> {
+        self.roll.critical
+    }
+}
+
+/// A change made to the subtotal of a label, by `ExpressionResult::transformed`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transform {
+    /// Leave the subtotal as it is.
+    Keep,
+    /// Halve the subtotal, rounding down.
+    Halve,
+    Double,
+    /// Discard the subtotal entirely.
+    Zero,
+}
+impl Transform {
+    pub fn apply(self, value: i64) -> Result<i64, Error> {
+        match self {
+            Transform::Keep => Ok(value),
+            Transform::Halve => crate::floor_div(value, 2),
+            Transform::Double => crate::checked_add(value, value),
+            Transform::Zero => Ok(0),
+        }
+    }
+}
+
+type RolledNode = Node<(Expr, EvaluatedTerm)>;
+
+fn node_value(node: &RolledNode) -> Result<i64, Error> {
+    crate::eval_node(node, &mut |(_, x): &(Expr, EvaluatedTerm)| Ok(x.value()))
+}
+
+fn is_unlabeled(node: &RolledNode) -> bool {
+    node.leaves().iter().all(|(x, _)| x.label.is_none())
+}
+
+/// The label of every term in `node`, if they all have the same one.
+fn common_label(node: &RolledNode) -> Option<&str> {
+    let leaves = node.leaves();
+    let mut labels = leaves.iter().map(|(x, _)| x.label.as_deref());
+    let first = labels.next().flatten();
+    if labels.all(|x| x == first) {
+        first
+    } else {
+        None
+    }
+}
+
+/// Split `node` into labeled parts summing to its value.
+/// See `ExpressionResult::labeled_operands`.
+fn labeled_parts<'a>(node: &'a RolledNode, parts: &mut Vec<(Option<&'a str>, i64)>) -> Result<(), Error> {
+    let start = parts.len();
+    match node {
+        Node::Sum(xs) => {
+            for x in xs {
+                labeled_parts(x, parts)?;
+            }
+        }
+        Node::Group(x) | Node::Floor(x) => labeled_parts(x, parts)?,
+        Node::Neg(x) => {
+            labeled_parts(x, parts)?;
+            for (_, value) in &mut parts[start..] {
+                *value = value.checked_neg().ok_or(Error::OverflowPositive(crate::OverflowPositive))?;
+            }
+        }
+        Node::Product(lhs, Operator::Multiply, rhs) if is_unlabeled(lhs) || is_unlabeled(rhs) => {
+            let (split, factor) = if is_unlabeled(rhs) { (lhs, rhs) } else { (rhs, lhs) };
+            let factor = node_value(factor)?;
+            labeled_parts(split, parts)?;
+            for (_, value) in &mut parts[start..] {
+                *value = crate::checked_mul(*value, factor)?;
+            }
+        }
+        _ => parts.push((common_label(node), node_value(node)?)),
+    }
+    Ok(())
+}
+
 #[derive(Debug, Clone, Copy)]
 pub(crate) enum TotalPosition {
     Left,
@@ -59,6 +379,11 @@ pub(crate) enum TermSeparator {
     PlusSign,
     Comma,
 }
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum ExpressionSeparator {
+    Semicolon,
+    Newline,
+}
 
 /// Formatting options for dice expressions.
 /// Necessary for stability of user facing output,
@@ -89,6 +414,8 @@ pub struct FormatOptions {
     pub(crate) term_separators: TermSeparator,
     pub(crate) term_parentheses: bool,
     pub(crate) term_list_parentheses: bool,
+    pub(crate) expression_separators: ExpressionSeparator,
+    pub(crate) mark_criticals: bool,
 }
 #[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl FormatOptions {
@@ -101,6 +428,8 @@ impl FormatOptions {
             term_separators: TermSeparator::PlusSign,
             term_parentheses: true,
             term_list_parentheses: false,
+            expression_separators: ExpressionSeparator::Semicolon,
+            mark_criticals: false,
         }
     }
     /// Crate internal API. Do not mark public.
@@ -170,6 +499,30 @@ impl FormatOptions {
         self.term_list_parentheses = false;
         self
     }
+    /// Separate the results of several expressions rolled together
+    /// with semicolons, side by side. `Expression1; Expression2; ...`
+    /// This is the current default.
+    pub fn expression_semicolons(mut self) -> Self {
+        self.expression_separators = ExpressionSeparator::Semicolon;
+        self
+    }
+    /// Put the results of several expressions rolled together on lines of their own.
+    pub fn expression_lines(mut self) -> Self {
+        self.expression_separators = ExpressionSeparator::Newline;
+        self
+    }
+    /// Mark dice that rolled a crit, `**20**` for
+    /// critical successes and `__1__` for critical failures.
+    pub fn mark_criticals(mut self) -> Self {
+        self.mark_criticals = true;
+        self
+    }
+    /// Don't mark dice that rolled a crit.
+    /// This is the current default.
+    pub fn no_mark_criticals(mut self) -> Self {
+        self.mark_criticals = false;
+        self
+    }
 }
 impl Default for FormatOptions {
     fn default() -> Self {
@@ -177,11 +530,116 @@ impl Default for FormatOptions {
     }
 }
 
+/// Classification of a die in a dice term that counts successes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Success,
+    Failure,
+    Neither,
+}
+
+/// Classification of a die whose face fell in a crit range, like `d20cs>19cf1`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Critical {
+    Success,
+    Failure,
+}
+
+/// The outcome of a single die in a dice term.
+#[derive(Debug, Clone)]
+pub(crate) struct DieRoll {
+    /// Faces replaced by rerolls, in the order they were rolled.
+    pub(crate) rerolled: Vec<i64>,
+    /// The face rolled, after any rerolls.
+    pub(crate) face: i64,
+    /// Extra rolls from explosions, in the order they were rolled,
+    /// already reduced by one for penetrating dice.
+    pub(crate) chain: Vec<i64>,
+    /// Whether `chain` was compounded into a single value.
+    pub(crate) compounded: bool,
+    /// The face together with its explosions.
+    pub(crate) value: i64,
+    /// Whether this die counts towards the total of its term.
+    /// Dice are only discarded by keep or drop modifiers.
+    pub(crate) kept: bool,
+    /// Only present on kept dice in terms that count successes.
+    pub(crate) outcome: Option<Outcome>,
+    /// Whether the face fell in a crit range of its term.
+    pub(crate) critical: Option<Critical>,
+}
+impl DieRoll {
+    pub(crate) fn new(face: i64) -> Self {
+        Self {
+            rerolled: Vec::new(),
+            face,
+            chain: Vec::new(),
+            compounded: false,
+            value: face,
+            kept: true,
+            outcome: None,
+            critical: None,
+        }
+    }
+    /// Number of rolls shown when this is displayed.
+    /// Compounded explosions display as a single roll.
+    pub(crate) fn shown(&self) -> usize {
+        self.rerolled.len() + if self.compounded { 1 } else { 1 + self.chain.len() }
+    }
+    /// `[+]`, `[-]` or `[ ]`.
+    fn format_fate(&self) -> &'static str {
+        match self.value {
+            1 => "[+]",
+            -1 => "[-]",
+            _ => "[ ]",
+        }
+    }
+    /// `6! + 4` for exploded dice, `10!!` for compounded ones,
+    /// `~~1~~ 5` for rerolled ones, and `9✓` or `1✗` for successes and failures.
+    /// Dice that were dropped are struck through whole, without their history.
+    /// Marked crits are `**20**` or `__1__`.
+    fn format(&self, sign: Sign, mark_criticals: bool) -> String {
+        let critical = if mark_criticals { self.critical } else { None };
+        if !self.kept {
+            return format!("~~{}~~", self.value);
+        }
+        let mut nstr = String::new();
+        for x in &self.rerolled {
+            nstr.push_str(&format!("~~{}~~ ", x));
+        }
+        let rolls = if self.chain.is_empty() {
+            mark(self.value, critical)
+        } else if self.compounded {
+            format!("{}!!", mark(self.value, critical))
+        } else {
+            let mut links = format!("{}!", mark(self.face, critical));
+            let last = self.chain.len() - 1;
+            for (i, x) in self.chain.iter().enumerate() {
+                links.push_str(&format!(" {} {}{}", sign, x, if i < last { "!" } else { "" }));
+            }
+            links
+        };
+        nstr.push_str(&rolls);
+        match self.outcome {
+            Some(Outcome::Success) => nstr.push('✓'),
+            Some(Outcome::Failure) => nstr.push('✗'),
+            _ => (),
+        }
+        nstr
+    }
+}
+
 #[derive(Debug, Clone)]
 pub(crate) struct RolledDie {
     pub(crate) total: i64,
-    pub(crate) parts: Vec<i64>,
+    pub(crate) parts: Vec<DieRoll>,
     pub(crate) sign_part: Sign,
+    /// Whether `total` counts successes, rather than summing `parts`.
+    pub(crate) counting: bool,
+    /// Whether `parts` are Fate dice, showing `-1`, `0` or `+1`.
+    pub(crate) fate: bool,
+    /// Number of rolls shown when this is displayed.
+    /// Kept apart from `parts`, since slim rolls discard those.
+    pub(crate) shown: usize,
 }
 impl Neg for RolledDie {
     type Output = Self;
@@ -190,19 +648,50 @@ impl Neg for RolledDie {
             total: -self.total,
             sign_part: -self.sign_part,
             parts: self.parts,
+            counting: self.counting,
+            fate: self.fate,
+            shown: self.shown,
         }
     }
 }
 impl RolledDie {
+    pub(crate) fn new(total: i64, parts: Vec<DieRoll>, counting: bool, fate: bool) -> Self {
+        Self {
+            total,
+            shown: parts.iter().map(DieRoll::shown).sum(),
+            parts,
+            sign_part: Sign::Positive,
+            counting,
+            fate,
+        }
+    }
+    /// A term whose dice weren't kept, that still displays
+    /// like it did when summarized.
+    pub(crate) fn slim(total: i64, shown: usize, counting: bool, fate: bool) -> Self {
+        Self {
+            total,
+            parts: Vec::new(),
+            sign_part: Sign::Positive,
+            counting,
+            fate,
+            shown,
+        }
+    }
+    /// Whether this is displayed as a list of its parts.
+    /// Success counts and Fate dice are, regardless of how many dice they have.
+    fn is_listing(&self) -> bool {
+        self.shown > 0 && (self.counting || self.fate || self.shown > 1)
+    }
     fn format(&self, options: FormatOptions) -> String {
         let FormatOptions {
             summarize_terms,
             ignore_sign,
+            mark_criticals,
             ..
         } = options;
         if summarize_terms {
             format!("{}", self.total)
-        } else if self.parts.len() > 1 {
+        } else if self.is_listing() && !self.parts.is_empty() {
             let mut iter = self.parts.iter();
             let first_sign = if !ignore_sign {
                 match self.sign_part {
@@ -212,26 +701,47 @@ impl RolledDie {
             } else {
                 ""
             };
-            let mut nstr = format!(
-                "{}{}",
-                if !ignore_sign { first_sign } else { "" },
-                iter.next().unwrap()
-            );
             let sign_part = if !ignore_sign {
                 self.sign_part
             } else {
                 Sign::Positive
             };
+            if self.fate {
+                let faces: Vec<_> = iter.map(|x| x.format_fate()).collect();
+                return format!("{}{}", first_sign, faces.join(" "));
+            }
+            let mut nstr = format!(
+                "{}{}",
+                if !ignore_sign { first_sign } else { "" },
+                iter.next().unwrap().format(sign_part, mark_criticals)
+            );
             for x in iter {
-                nstr.push_str(&format!(" {} {}", sign_part, x))
+                if self.counting {
+                    nstr.push_str(&format!(", {}", x.format(sign_part, mark_criticals)))
+                } else {
+                    nstr.push_str(&format!(" {} {}", sign_part, x.format(sign_part, mark_criticals)))
+                }
             }
             // nstr.push_str(&format!(" = {}", self.total));
             nstr
         } else {
-            format!("{}", if ignore_sign { self.total.abs() } else { self.total })
+            let total = if ignore_sign { self.total.abs() } else { self.total };
+            match self.parts.as_slice() {
+                [x] if mark_criticals && x.kept && !self.counting => mark(total, x.critical),
+                _ => format!("{}", total),
+            }
         }
     }
 }
+
+/// Surround a number with the marker for a crit, if there is one.
+fn mark(value: i64, critical: Option<Critical>) -> String {
+    match critical {
+        Some(Critical::Success) => format!("**{}**", value),
+        Some(Critical::Failure) => format!("__{}__", value),
+        None => format!("{}", value),
+    }
+}
 impl Display for RolledDie {
     fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
         write!(f, "{}", self.format(FormatOptions::new()))
@@ -263,10 +773,11 @@ impl EvaluatedTerm {
             EvaluatedTerm::Constant(x) => *x,
         }
     }
-    pub(crate) fn parts(&self) -> &[i64] {
+    /// Whether this term is displayed as a list of its parts.
+    pub(crate) fn is_listing(&self) -> bool {
         match self {
-            EvaluatedTerm::Die(x) => &x.parts,
-            EvaluatedTerm::Constant(x) => slice::from_ref(x),
+            EvaluatedTerm::Die(x) => x.is_listing(),
+            EvaluatedTerm::Constant(_) => false,
         }
     }
     pub(crate) fn sign(&self) -> Sign {
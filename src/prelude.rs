@@ -7,5 +7,5 @@ pub use crate::{builder::RollBuilder, Error as MiceError, FormatOptions as MiceF
 #[cfg(feature = "thread_rng")]
 pub use crate::{
     expose::{roll_tuples, tuple_vec},
-    roll,
+    roll, roll_list,
 };
@@ -1,8 +1,9 @@
 //! Nice to have utilities that aren't core to dice
 //! manipulation itself, just handy for some reason.
-#[cfg(feature = "thread_rng")]
 use crate::parse::{Expression, Term};
-use crate::{Error, ExpressionResult, parse::ParseError};
+use crate::{Error, parse::ParseError};
+#[cfg(feature = "thread_rng")]
+use crate::{BatchResult, ExpressionResult};
 use thiserror::Error;
 
 #[derive(Debug, Clone, Copy, Error)]
@@ -23,18 +24,25 @@ impl From<ParseError> for UtilError {
     }
 }
 
+#[cfg(feature = "thread_rng")]
 type UResult = Result<ExpressionResult, UtilError>;
 
-#[cfg(feature = "thread_rng")]
 fn exceeds_cap(dice: &Expression, cap: i64) -> bool {
-    let mut roll_count = 0;
+    steps(dice, cap) > cap
+}
+
+/// Number of evaluation steps in rolling `dice`,
+/// or some number over `cap`, if it would exceed that.
+fn steps(dice: &Expression, cap: i64) -> i64 {
+    let mut roll_count: i64 = 0;
     for term in dice.terms() {
         match term {
-            Term::Dice(d) => if d.size > 1 {
-                roll_count += d.number;
+            Term::Dice(d) | Term::Percentile(d) => if d.size > 1 || d.max_rolls() > d.number {
+                roll_count = roll_count.saturating_add(d.max_rolls());
             } else {
                 roll_count += 1;
             },
+            Term::Fate(d) => roll_count = roll_count.saturating_add(d.number),
             // This branch only saves time
             // in the worst case - when there's
             // a truly obscene number of terms.
@@ -42,10 +50,10 @@ fn exceeds_cap(dice: &Expression, cap: i64) -> bool {
         }
         // Prevent worst case performance
         if roll_count > cap {
-            return true;
+            return roll_count;
         }
     }
-    roll_count > cap
+    roll_count
 }
 
 mod private {
@@ -65,6 +73,9 @@ pub trait ExpressionExt: private::Sealed {
     ///
     /// Constant terms take one step, dice terms take one step for each die.
     /// An `Nd1` dice term counts as a constant term for the purpose of this sum.
+    /// Exploding and rerolled dice are counted as if every die exploded
+    /// and was rerolled the maximum number of times,
+    /// `parse::EXPLOSION_LIMIT` and `parse::REROLL_LIMIT`.
     fn exceeds_cap(&self, cap: i64) -> bool;
 }
 impl ExpressionExt for Expression {
@@ -101,7 +112,7 @@ pub fn roll_exp_capped(dice: Expression, cap: i64) ->
     Result<Result<ExpressionResult, crate::Error>, ExceededCap>
 {
     if !exceeds_cap(&dice, cap) {
-        Ok(crate::roll_expr_iter_with(&mut rand::thread_rng(), dice.into_iter()))
+        Ok(crate::roll_expression_with::<crate::Checked, _>(&mut rand::thread_rng(), &dice))
     } else {
         Err(ExceededCap)
     }
@@ -117,3 +128,25 @@ pub fn roll_capped(input: &str, cap: i64) -> UResult {
         Err(e) => Err(e.into()),
     }
 }
+
+/// Like `roll_capped`, but accepting a repetition prefix like `12x 1d20+3`.
+/// The cap applies to all repetitions together.
+/// ```
+/// # use mice::util::{roll_batch_capped, UtilError};
+/// let goblins = roll_batch_capped("12x 1d20+3", 100)?;
+/// assert_eq!(goblins.results().len(), 12);
+/// assert!(matches!(roll_batch_capped("12x 10d20", 100), Err(UtilError::ExceededCap)));
+/// # Ok::<(), UtilError>(())
+/// ```
+#[cfg(feature = "thread_rng")]
+pub fn roll_batch_capped(input: &str, cap: i64) -> Result<BatchResult, UtilError> {
+    let (count, dice) = crate::parse::wrap_batch(input)?;
+    if steps(&dice, cap).saturating_mul(count) > cap {
+        return Err(ExceededCap.into());
+    }
+    let mut rng = rand::thread_rng();
+    let results = (0..count)
+        .map(|_| crate::roll_expression_with::<crate::Checked, _>(&mut rng, &dice))
+        .collect::<Result<_, _>>()?;
+    Ok(BatchResult::new(results))
+}